@@ -1,5 +1,6 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use ising_lib::lattice2d::Lattice2d;
+use ising_lib::packed_lattice2d::PackedLattice2d;
 use ising_lib::monte_carlo_measurement::{MonteCarlo, MonteCarloParams};
 
 // Criterion links:
@@ -16,14 +17,67 @@ fn bench_flip_100_spins(c: &mut Criterion) {
     });
 }
 
+// Full sweeps of a larger lattice, sequential vs checkerboard in parallel
+fn bench_sweep_512(c: &mut Criterion) {
+    let mut lattice = Lattice2d::new_basic([512, 512]);
+    c.bench_function("sweep 512x512", move |b| {
+        b.iter(|| {
+            lattice.update_n(512 * 512);
+        })
+    });
+}
+
+fn bench_sweep_checkerboard_512(c: &mut Criterion) {
+    let mut lattice = Lattice2d::new_basic([512, 512]);
+    c.bench_function("sweep checkerboard 512x512", move |b| {
+        b.iter(|| {
+            lattice.sweep_checkerboard();
+        })
+    });
+}
+
+fn bench_sweep_checkerboard_parallel_512(c: &mut Criterion) {
+    let mut lattice = Lattice2d::new_basic([512, 512]);
+    c.bench_function("sweep checkerboard parallel 512x512", move |b| {
+        b.iter(|| {
+            lattice.sweep_checkerboard_parallel();
+        })
+    });
+}
+
+fn bench_sweep_packed_512(c: &mut Criterion) {
+    let mut lattice = PackedLattice2d::new_basic([512, 512]);
+    c.bench_function("sweep packed 512x512", move |b| {
+        b.iter(|| {
+            lattice.sweep();
+        })
+    });
+}
+
+// The size the parallel sweeps are meant for, where a sweep takes long
+// enough that only a few samples are affordable
+fn bench_sweep_4096(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sweep 4096x4096");
+    group.sample_size(10);
+    let mut lattice = Lattice2d::new_basic([4096, 4096]);
+    group.bench_function("sequential", |b| b.iter(|| lattice.sweep()));
+    group.bench_function("checkerboard", |b| b.iter(|| lattice.sweep_checkerboard()));
+    group.bench_function("checkerboard parallel", |b| b.iter(|| lattice.sweep_checkerboard_parallel()));
+    let mut packed = PackedLattice2d::new_basic([4096, 4096]);
+    group.bench_function("packed", |b| b.iter(|| packed.sweep()));
+    group.finish();
+}
+
 // Monte Carlo benchmarks
 fn bench_sample_energy(c: &mut Criterion) {
     let mut lattice = Lattice2d::new_basic([50, 50]);
     let params = MonteCarloParams {
-        n_runs: 500,
-        flips_to_skip: 50, // 1500_000,
+        n_runs: 10,
+        sweeps_to_skip: 1,
         samples_per_run: 5,
-        flips_to_skip_between_samples: 10,
+        sweeps_between_samples: 1,
+        max_threads: None,
+        start_from_current: false,
     };
     c.bench_function("sample energy", move |b| {
         b.iter(|| {
@@ -35,14 +89,16 @@ fn bench_sample_energy(c: &mut Criterion) {
 fn bench_sample_energy_parallel(c: &mut Criterion) {
     let mut lattice = Lattice2d::new_basic([50, 50]);
     let params = MonteCarloParams {
-        n_runs: 500,
-        flips_to_skip: 50, // 1500_000,
+        n_runs: 10,
+        sweeps_to_skip: 1,
         samples_per_run: 5,
-        flips_to_skip_between_samples: 10,
+        sweeps_between_samples: 1,
+        max_threads: None,
+        start_from_current: false,
     };
     c.bench_function("sample energy parallel", move |b| {
         b.iter(|| {
-            let _erg_samples = lattice.sample_energy_parallel(&params);
+            let _erg_samples = lattice.sample_energy_parallel(&params).unwrap();
         })
     });
 }
@@ -50,6 +106,11 @@ fn bench_sample_energy_parallel(c: &mut Criterion) {
 criterion_group!(
     benches,
     bench_flip_100_spins,
+    bench_sweep_512,
+    bench_sweep_checkerboard_512,
+    bench_sweep_checkerboard_parallel_512,
+    bench_sweep_packed_512,
+    bench_sweep_4096,
     bench_sample_energy,
     bench_sample_energy_parallel
 );
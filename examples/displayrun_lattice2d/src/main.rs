@@ -1,22 +1,16 @@
 use ising_lib::lattice2d::{Lattice2d,UpdateRule,SpinType,InitType};
-use std::{thread, time};
+use ising_lib::viewer::Viewer;
 
-fn main() {
-    let seven_millis = time::Duration::from_millis(7);  // wait 7 millis to slow simulation
-    // let mut lattice = Lattice2d::new_basic([45,145]);   // create a lattice
-    let mut lattice = Lattice2d::new([45,125],
-                                     UpdateRule::Metropolis,
-                                     SpinType::SpinHalf,
-                                     InitType::Random,
-                                     1.0f64,
-                                     0.0f64,
-                                     0.20f64);
-    lattice.disp_terminal();
-    for _ in 0..1200 {                  // 1200 frames 
-        for _ in 0..2_000 {
-            lattice.update();           // update lattice 2000 times between each frame
-        }
-        thread::sleep(seven_millis);    // wait 7 millis for smooth flip-card video display
-        lattice.disp_terminal();        // display the lattice in terminal
-    }
+fn main() -> std::io::Result<()> {
+    // let mut lattice = Lattice2d::new_basic([90,145]);   // create a lattice
+    let lattice = Lattice2d::new([90,125],
+                                 UpdateRule::Metropolis,
+                                 SpinType::SpinHalf,
+                                 InitType::Random,
+                                 1.0f64,
+                                 0.0f64,
+                                 0.20f64);
+    let mut viewer = Viewer::new(lattice);
+    viewer.sweeps_per_frame = 1;    // sweep the lattice once between each frame
+    viewer.run(30.0)                // 30 frames per second, ↑/↓ changes beta, ←/→ the field, q quits
 }
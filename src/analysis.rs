@@ -0,0 +1,109 @@
+//! Helpers for post-processing samples produced by the MonteCarlo
+//! trait, such as autocorrelation functions and simple least squares
+//! fits for extracting relaxation times and exponents.
+
+/// Normalized autocorrelation function of one or more time series
+/// of the same observable (e.g. one per run)
+///
+/// ```text
+/// ρ(t) = (<x(t0) x(t0 + t)> - <x>²) / (<x²> - <x>²)
+/// ```
+///
+/// The mean and variance are pooled over all series, and the average
+/// over t0 runs over every pair of points within a series. Returns
+/// ρ(0..=max_lag); lags with no pairs, or a series with zero variance,
+/// give NaN.
+pub fn autocorrelation(series: &[Vec<f64>], max_lag: usize) -> Vec<f64> {
+    let n_points: usize = series.iter().map(|s| s.len()).sum();
+    let mean = series.iter().flatten().sum::<f64>() / n_points as f64;
+    let mut corr = vec![0.0; max_lag + 1];
+    for (lag, c) in corr.iter_mut().enumerate() {
+        let mut sum = 0.0;
+        let mut count = 0;
+        for s in series.iter().filter(|s| s.len() > lag) {
+            for (x0, xt) in s.iter().zip(s[lag..].iter()) {
+                sum += (x0 - mean) * (xt - mean);
+                count += 1;
+            }
+        }
+        *c = sum / count as f64;
+    }
+    let variance = corr[0];
+    corr.iter().map(|c| c / variance).collect()
+}
+
+/// Least squares fit of a straight line y = a + b x, returns (a, b)
+pub fn fit_line(xs: &[f64], ys: &[f64]) -> (f64, f64) {
+    let n = xs.len() as f64;
+    let x_mean = xs.iter().sum::<f64>() / n;
+    let y_mean = ys.iter().sum::<f64>() / n;
+    let mut sxy = 0.0;
+    let mut sxx = 0.0;
+    for (x, y) in xs.iter().zip(ys) {
+        sxy += (x - x_mean) * (y - y_mean);
+        sxx += (x - x_mean) * (x - x_mean);
+    }
+    let slope = sxy / sxx;
+    (y_mean - slope * x_mean, slope)
+}
+
+/// Fits an exponential decay y = exp(-t / τ) and returns the relaxation
+/// time τ (in the units of `times`)
+///
+/// The fit is a straight line through ln(y) over the leading points
+/// with y > 0.05, past which the autocorrelation is usually dominated by
+/// noise. Returns NaN if fewer than two points qualify.
+pub fn fit_exponential_decay(times: &[f64], values: &[f64]) -> f64 {
+    let n_points = values.iter().take_while(|&&y| y > 0.05).count();
+    if n_points < 2 {
+        return f64::NAN;
+    }
+    let log_values: Vec<f64> = values[..n_points].iter().map(|y| y.ln()).collect();
+    let (_, slope) = fit_line(&times[..n_points], &log_values);
+    -1.0 / slope
+}
+
+/// Fits a power law y = A x^k through a log-log line, returns (A, k)
+pub fn fit_power_law(xs: &[f64], ys: &[f64]) -> (f64, f64) {
+    let log_xs: Vec<f64> = xs.iter().map(|x| x.ln()).collect();
+    let log_ys: Vec<f64> = ys.iter().map(|y| y.ln()).collect();
+    let (intercept, slope) = fit_line(&log_xs, &log_ys);
+    (intercept.exp(), slope)
+}
+
+/// Estimates the dynamic exponent z from relaxation times measured on
+/// lattices of different linear size L, assuming τ ∝ L^z (which holds
+/// at the critical temperature)
+pub fn dynamic_exponent(lengths: &[usize], relaxation_times: &[f64]) -> f64 {
+    let lengths: Vec<f64> = lengths.iter().map(|&l| l as f64).collect();
+    fit_power_law(&lengths, relaxation_times).1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_autocorrelation() {
+        let alternating = vec![vec![1.0, -1.0, 1.0, -1.0, 1.0, -1.0]];
+        let corr = autocorrelation(&alternating, 2);
+        assert!((corr[0] - 1.0).abs() < 1e-12);
+        assert!((corr[1] + 1.0).abs() < 1e-12);
+        assert!((corr[2] - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_fit_exponential_decay() {
+        let times: Vec<f64> = (0..20).map(|t| t as f64 * 0.5).collect();
+        let values: Vec<f64> = times.iter().map(|t| (-t / 3.0).exp()).collect();
+        assert!((fit_exponential_decay(&times, &values) - 3.0).abs() < 1e-9);
+        assert!(fit_exponential_decay(&times, &[0.0; 20]).is_nan());
+    }
+
+    #[test]
+    fn test_dynamic_exponent() {
+        let lengths = [8, 16, 32, 64];
+        let taus: Vec<f64> = lengths.iter().map(|&l| 0.7 * (l as f64).powf(2.17)).collect();
+        assert!((dynamic_exponent(&lengths, &taus) - 2.17).abs() < 1e-9);
+    }
+}
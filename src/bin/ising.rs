@@ -0,0 +1,479 @@
+//! The `ising` command line tool, built with the `cli` feature. It runs
+//! a simulation described by a TOML config (or JSON, if the file name
+//! ends in `.json`):
+//!
+//! ```text
+//! ising run config.toml      samples the observables at every temperature
+//! ising sweep config.toml    averages the observables over a temperature sweep
+//! ising animate config.toml  records a run as a GIF or numbered PNG frames
+//! ising exact config.toml    exact thermodynamics of small lattices
+//! ```
+//!
+//! A config looks like this, all sections but `lattice` being optional:
+//!
+//! ```toml
+//! observables = ["energy", "abs_magnetization"]
+//!
+//! [lattice]
+//! dims = [32, 32]
+//! j = 1.0                      # default 1.0
+//! h = 0.0                      # default 0.0
+//! update_rule = "Metropolis"   # default Metropolis
+//! init_type = "Random"         # Random (default) or AllUp
+//! site_order = "Random"        # Random (default), Typewriter or Checkerboard
+//! seed = 42                    # default: seeded from the OS
+//!
+//! [monte_carlo]
+//! n_runs = 4
+//! sweeps_to_skip = 500
+//! samples_per_run = 100
+//! sweeps_between_samples = 5
+//!
+//! [temperature]
+//! scale = "beta"               # whether the values are beta (default) or the temperature
+//! values = [0.4, 0.44]         # explicit values, or
+//! range = { start = 0.3, end = 0.6, steps = 7 }
+//! anneal = true                # sweep: carry the spins from one step to the next
+//!
+//! [output]
+//! path = "results.csv"         # default: standard output
+//! format = "Csv"               # Csv (default), JsonLines or Binary
+//!
+//! [animation]
+//! path = "run.gif"             # a .gif file, or a directory for PNG frames
+//! frames = 100
+//! sweeps_between_frames = 1
+//! scale = 4
+//! color_map = "Grayscale"      # Grayscale, BlueRed or { Gradient = [[r, g, b], ...] }
+//! delay = 5                    # hundredths of a second per frame
+//! ```
+//!
+//! Results are written with `results_writer`, with beta and h as the
+//! parameters of every record.
+
+use ising_lib::exact;
+use ising_lib::image_export::{self, ColorMap, FrameFormat, GifRecorder, ImageOptions};
+use ising_lib::lattice2d::{InitType, Lattice2d, SiteOrder, UpdateRule};
+use ising_lib::observable::{self, Observable, ObservableValue};
+use ising_lib::parameter_sweep::{self, SweepParameter, SweepParams};
+use ising_lib::results_writer::{Format, ResultsWriter};
+use serde::Deserialize;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+const USAGE: &str = "usage: ising <run|sweep|animate|exact> <config.toml|config.json>";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    lattice: LatticeConfig,
+    monte_carlo: Option<MonteCarloConfig>,
+    #[serde(default)]
+    temperature: TemperatureConfig,
+    #[serde(default = "default_observables")]
+    observables: Vec<String>,
+    #[serde(default)]
+    output: OutputConfig,
+    animation: Option<AnimationConfig>,
+}
+
+fn default_observables() -> Vec<String> {
+    vec!["energy".to_owned(), "magnetization".to_owned()]
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LatticeConfig {
+    dims: [usize; 2],
+    #[serde(default = "default_j")]
+    j: f64,
+    #[serde(default)]
+    h: f64,
+    #[serde(default = "default_update_rule")]
+    update_rule: UpdateRule,
+    #[serde(default = "default_init_type")]
+    init_type: InitType,
+    #[serde(default = "default_site_order")]
+    site_order: SiteOrder,
+    seed: Option<u64>,
+}
+
+fn default_j() -> f64 {
+    1.0
+}
+
+fn default_update_rule() -> UpdateRule {
+    UpdateRule::Metropolis
+}
+
+fn default_init_type() -> InitType {
+    InitType::Random
+}
+
+fn default_site_order() -> SiteOrder {
+    SiteOrder::Random
+}
+
+/// The sampling schedule; `run` and `sweep` sample on one thread, so
+/// unlike `MonteCarloParams` there is no `max_threads`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MonteCarloConfig {
+    n_runs: usize,
+    sweeps_to_skip: usize,
+    samples_per_run: usize,
+    sweeps_between_samples: usize,
+}
+
+#[derive(Clone, Copy, Deserialize, Default, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Scale {
+    #[default]
+    Beta,
+    Temperature,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Range {
+    start: f64,
+    end: f64,
+    steps: usize,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct TemperatureConfig {
+    #[serde(default)]
+    scale: Scale,
+    values: Option<Vec<f64>>,
+    range: Option<Range>,
+    #[serde(default)]
+    anneal: bool,
+}
+
+impl TemperatureConfig {
+    /// The inverse temperatures, in the order they are visited
+    fn betas(&self) -> Result<Vec<f64>, String> {
+        let values = match (&self.values, &self.range) {
+            (Some(values), None) => values.clone(),
+            (None, Some(range)) => parameter_sweep::linspace(range.start, range.end, range.steps),
+            (None, None) => vec![0.43],
+            (Some(_), Some(_)) => return Err("give either temperature.values or temperature.range".to_owned()),
+        };
+        if values.is_empty() {
+            return Err("no temperatures given".to_owned());
+        }
+        values.iter()
+            .map(|&x| match self.scale {
+                Scale::Beta if x >= 0.0 => Ok(x),
+                Scale::Temperature if x > 0.0 => Ok(1.0 / x),
+                _ => Err(format!("invalid {:?} {}", self.scale, x).to_lowercase()),
+            })
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OutputConfig {
+    path: Option<PathBuf>,
+    #[serde(default = "default_format")]
+    format: Format,
+}
+
+fn default_format() -> Format {
+    Format::Csv
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            path: None,
+            format: default_format(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnimationConfig {
+    path: PathBuf,
+    #[serde(default = "default_frames")]
+    frames: usize,
+    #[serde(default = "default_one")]
+    sweeps_between_frames: usize,
+    #[serde(default = "default_one")]
+    scale: usize,
+    #[serde(default = "default_color_map")]
+    color_map: ColorMap,
+    #[serde(default = "default_delay")]
+    delay: u16,
+}
+
+fn default_frames() -> usize {
+    100
+}
+
+fn default_one() -> usize {
+    1
+}
+
+fn default_color_map() -> ColorMap {
+    ColorMap::Grayscale
+}
+
+fn default_delay() -> u16 {
+    5
+}
+
+impl Config {
+    /// Reads a config, as JSON if the file name ends in `.json` and as
+    /// TOML otherwise
+    fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text, path.extension().is_some_and(|ext| ext == "json"))
+    }
+
+    fn parse(text: &str, json: bool) -> Result<Self, Box<dyn Error>> {
+        let config: Config = if json { serde_json::from_str(text)? } else { toml::from_str(text)? };
+        config.temperature.betas()?;
+        config.observables()?;
+        Ok(config)
+    }
+
+    /// A lattice at the first temperature
+    fn lattice(&self) -> Result<Lattice2d, Box<dyn Error>> {
+        let mut builder = Lattice2d::builder(self.lattice.dims)
+            .update_rule(self.lattice.update_rule)
+            .init_type(self.lattice.init_type)
+            .site_order(self.lattice.site_order)
+            .j(self.lattice.j)
+            .h(self.lattice.h)
+            .beta(self.temperature.betas()?[0]);
+        if let Some(seed) = self.lattice.seed {
+            builder = builder.seed(seed);
+        }
+        Ok(builder.build()?)
+    }
+
+    fn observables(&self) -> Result<Vec<&'static (dyn Observable<Lattice2d> + Sync)>, String> {
+        self.observables.iter()
+            .map(|name| {
+                observable::builtin(name).ok_or_else(|| {
+                    format!("unknown observable {:?}, expected one of {}", name, observable::BUILTIN_NAMES.join(", "))
+                })
+            })
+            .collect()
+    }
+
+    fn monte_carlo(&self) -> Result<&MonteCarloConfig, String> {
+        self.monte_carlo.as_ref().ok_or_else(|| "missing the [monte_carlo] section".to_owned())
+    }
+
+    /// Opens the output, standard output if no path is given
+    fn results_writer(
+        &self,
+        parameter_names: &[&str],
+        observable_names: &[&str],
+    ) -> io::Result<ResultsWriter<Box<dyn Write>>> {
+        let writer: Box<dyn Write> = match &self.output.path {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(io::stdout()),
+        };
+        ResultsWriter::new(writer, self.output.format, parameter_names, observable_names)
+    }
+}
+
+/// Samples the observables at every temperature, writing every sample
+/// as soon as it is taken
+fn run(config: &Config) -> Result<(), Box<dyn Error>> {
+    let params = config.monte_carlo()?;
+    let observables = config.observables()?;
+    let names: Vec<&str> = observables.iter().map(|o| o.name()).collect();
+    let mut writer = config.results_writer(&["beta", "h"], &names)?;
+    let mut lattice = config.lattice()?;
+    for beta in config.temperature.betas()? {
+        lattice.beta = beta;
+        for run in 0..params.n_runs {
+            lattice.reset_spins();
+            lattice.sweep_n(params.sweeps_to_skip);
+            for sample in 0..params.samples_per_run {
+                lattice.sweep_n(params.sweeps_between_samples);
+                let values: Vec<ObservableValue> = observables.iter().map(|o| o.measure(&lattice)).collect();
+                writer.write_record(&[beta, lattice.h], run, sample, &values)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Averages the observables at every temperature, writing one record
+/// per step with the mean and standard error of every observable
+fn sweep(config: &Config) -> Result<(), Box<dyn Error>> {
+    let params = config.monte_carlo()?;
+    let observables = config.observables()?;
+    let observables: Vec<&dyn Observable<Lattice2d>> = observables.iter().map(|&o| o as _).collect();
+    let mut lattice = config.lattice()?;
+    let sweep_params = SweepParams {
+        parameter: SweepParameter::Beta,
+        values: config.temperature.betas()?,
+        anneal: config.temperature.anneal,
+        sweeps_to_skip: params.sweeps_to_skip,
+        samples_per_step: params.samples_per_run,
+        sweeps_between_samples: params.sweeps_between_samples,
+    };
+    let table = parameter_sweep::try_run_sweep(&mut lattice, &sweep_params, &observables)?;
+    let columns: Vec<String> = table.names.iter()
+        .flat_map(|name| [name.clone(), format!("{}_error", name)])
+        .collect();
+    let columns: Vec<&str> = columns.iter().map(|c| c.as_str()).collect();
+    let mut writer = config.results_writer(&["beta", "h"], &columns)?;
+    for row in table.rows.iter() {
+        let values: Vec<ObservableValue> = row.means.iter()
+            .zip(row.errors.iter())
+            .flat_map(|(&mean, &error)| [mean.into(), error.into()])
+            .collect();
+        writer.write_record(&[row.beta, row.h], 0, row.step, &values)?;
+    }
+    Ok(())
+}
+
+/// Records a run at the first temperature
+fn animate(config: &Config) -> Result<(), Box<dyn Error>> {
+    let animation = config.animation.as_ref().ok_or("missing the [animation] section")?;
+    let options = ImageOptions {
+        color_map: animation.color_map.clone(),
+        scale: animation.scale.max(1),
+    };
+    let mut lattice = config.lattice()?;
+    if animation.path.extension().is_some_and(|ext| ext == "gif") {
+        let writer = BufWriter::new(File::create(&animation.path)?);
+        let mut recorder = GifRecorder::new(writer, lattice.dims, options, animation.delay)?;
+        recorder.add_frame(&lattice)?;
+        recorder.record(&mut lattice, animation.frames.saturating_sub(1), animation.sweeps_between_frames)?;
+        recorder.into_inner()?.flush()?;
+    } else {
+        fs::create_dir_all(&animation.path)?;
+        image_export::save_frames(
+            &mut lattice,
+            &animation.path,
+            "frame_",
+            animation.frames,
+            animation.sweeps_between_frames,
+            &options,
+            FrameFormat::Png,
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the exact energy, specific heat, free energy and entropy per
+/// spin at every temperature (h = 0)
+fn exact(config: &Config) -> Result<(), Box<dyn Error>> {
+    let dims = config.lattice.dims;
+    // the cost of counting the states grows as 8^L with the shorter side L
+    if dims.contains(&0) || dims[0] * dims[1] > 126 || dims[0].min(dims[1]) > 6 {
+        return Err("exact results need a lattice of at most 126 sites, with a side of at most 6".into());
+    }
+    let n_sites = (dims[0] * dims[1]) as f64;
+    let dos = exact::density_of_states(dims, config.lattice.j);
+    let mut writer = config.results_writer(&["beta", "h"], &["energy", "specific_heat", "free_energy", "entropy"])?;
+    for (step, beta) in config.temperature.betas()?.into_iter().enumerate() {
+        let values: Vec<ObservableValue> = [
+            dos.internal_energy(beta),
+            dos.specific_heat(beta),
+            dos.free_energy(beta),
+            dos.entropy(beta),
+        ]
+        .iter()
+        .map(|&x| (x / n_sites).into())
+        .collect();
+        writer.write_record(&[beta, 0.0], 0, step, &values)?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() != 2 {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+    let command = match args[0].as_str() {
+        "run" => run,
+        "sweep" => sweep,
+        "animate" => animate,
+        "exact" => exact,
+        _ => {
+            eprintln!("unknown command {:?}\n{}", args[0], USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = Config::load(Path::new(&args[1])).and_then(|config| command(&config)) {
+        eprintln!("ising: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_toml() {
+        let config = Config::parse(
+            r#"
+            observables = ["energy", "abs_magnetization"]
+
+            [lattice]
+            dims = [8, 6]
+            site_order = "Checkerboard"
+            seed = 3
+
+            [monte_carlo]
+            n_runs = 2
+            sweeps_to_skip = 10
+            samples_per_run = 5
+            sweeps_between_samples = 1
+
+            [temperature]
+            scale = "temperature"
+            range = { start = 2.0, end = 4.0, steps = 3 }
+
+            [output]
+            format = "JsonLines"
+            "#,
+            false,
+        )
+        .unwrap();
+        assert_eq!(config.temperature.betas().unwrap(), vec![0.5, 1.0 / 3.0, 0.25]);
+        assert_eq!(config.lattice.j, 1.0);
+        assert_eq!(config.output.format, Format::JsonLines);
+        assert_eq!(config.monte_carlo().unwrap().samples_per_run, 5);
+        let lattice = config.lattice().unwrap();
+        assert_eq!(lattice.dims, [8, 6]);
+        assert_eq!(lattice.beta, 0.5);
+        assert_eq!(lattice.site_order, SiteOrder::Checkerboard);
+    }
+
+    #[test]
+    fn test_parse_json_and_errors() {
+        let config = Config::parse(r#"{"lattice": {"dims": [4, 4], "init_type": "AllUp"}}"#, true).unwrap();
+        assert_eq!(config.observables, default_observables());
+        assert_eq!(config.temperature.betas().unwrap(), vec![0.43]);
+        assert!(config.monte_carlo().is_err());
+
+        assert!(Config::parse("[lattice]\ndims = [4, 4]\nsize = 3", false).is_err());
+        let monte_carlo = "n_runs = 1\nsweeps_to_skip = 0\nsamples_per_run = 1\nsweeps_between_samples = 1";
+        assert!(Config::parse(&format!("[lattice]\ndims = [4, 4]\n[monte_carlo]\n{}", monte_carlo), false).is_ok());
+        let max_threads = format!("[lattice]\ndims = [4, 4]\n[monte_carlo]\n{}\nmax_threads = 2", monte_carlo);
+        assert!(Config::parse(&max_threads, false).is_err());
+        assert!(Config::parse("observables = [\"heat\"]\n[lattice]\ndims = [4, 4]", false).is_err());
+        assert!(Config::parse("[lattice]\ndims = [4, 4]\n[temperature]\nvalues = [-1.0]", false).is_err());
+        assert!(Config::parse("[lattice]\ndims = [4, 4]\n[temperature]\nvalues = []", false).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+//! Long runs can be split into pieces with a resumable sampler. It
+//! holds the whole state of a `MonteCarlo::sample_all_metrics` style
+//! run: the lattice with its random number generator, the progress
+//! through the runs, and the samples taken so far. With the `serde`
+//! feature the sampler can be serialized in any serde format as a
+//! checkpoint, and a sampler restored from it continues exactly where
+//! the saved one stopped. A seeded run gives the same samples however
+//! often it is interrupted. JSON checkpoints need serde_json's
+//! `float_roundtrip` feature to restore beta and h bit for bit.
+
+use crate::lattice2d::Lattice2d;
+use crate::monte_carlo_measurement::{MonteCarloParams, ObservableSamples};
+use crate::observable::{Observable, ObservableValue};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A sampler that can be advanced a few sweeps at a time, see `advance`
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ResumableSampler {
+    pub lattice: Lattice2d,
+    pub params: MonteCarloParams,
+    names: Vec<String>,                     // names of the observables being sampled
+    values: Vec<Vec<Vec<ObservableValue>>>, // values[observable][run][sample], filled so far
+    run: usize,                             // the current run
+    started: bool,                          // whether the spins were reset for the current run
+    sweeps_in_run: usize,                   // sweeps done in the current run
+    samples_in_run: usize,                  // samples taken in the current run
+}
+
+impl ResumableSampler {
+    /// Prepares to sample the observables, like `sample_all_metrics`
+    pub fn new(lattice: Lattice2d, params: MonteCarloParams, observables: &[&dyn Observable<Lattice2d>]) -> Self {
+        let values = vec![vec![Vec::with_capacity(params.samples_per_run); params.n_runs]; observables.len()];
+        ResumableSampler {
+            lattice,
+            params,
+            names: observables.iter().map(|o| o.name().to_owned()).collect(),
+            values,
+            run: 0,
+            started: false,
+            sweeps_in_run: 0,
+            samples_in_run: 0,
+        }
+    }
+
+    /// Returns true once every run is complete
+    pub fn is_done(&self) -> bool {
+        self.run >= self.params.n_runs
+    }
+
+    /// Sweeps done so far, over all runs
+    pub fn sweeps_done(&self) -> usize {
+        self.run * self.sweeps_per_run() + self.sweeps_in_run
+    }
+
+    /// Sweeps needed to complete every run
+    pub fn total_sweeps(&self) -> usize {
+        self.params.n_runs * self.sweeps_per_run()
+    }
+
+    fn sweeps_per_run(&self) -> usize {
+        self.params.sweeps_to_skip + self.params.samples_per_run * self.params.sweeps_between_samples
+    }
+
+    /// Continues sampling for at most max_sweeps sweeps, returns true
+    /// once every run is complete
+    ///
+    /// The observables must be the ones the sampler was created with,
+    /// in the same order.
+    pub fn advance(&mut self, max_sweeps: usize, observables: &[&dyn Observable<Lattice2d>]) -> bool {
+        assert!(
+            observables.iter().map(|o| o.name()).eq(self.names.iter().map(|n| n.as_str())),
+            "The observables differ from the ones the sampler was created with"
+        );
+        let mut budget = max_sweeps;
+        while !self.is_done() {
+            if !self.started {
+                self.lattice.reset_spins();
+                self.started = true;
+            }
+            // take the samples that are due
+            while self.samples_in_run < self.params.samples_per_run
+                && self.sweeps_in_run
+                    == self.params.sweeps_to_skip + (self.samples_in_run + 1) * self.params.sweeps_between_samples
+            {
+                for (observable, samples) in observables.iter().zip(self.values.iter_mut()) {
+                    samples[self.run].push(observable.measure(&self.lattice));
+                }
+                self.samples_in_run += 1;
+            }
+            if self.samples_in_run == self.params.samples_per_run {
+                self.run += 1;
+                self.started = false;
+                self.sweeps_in_run = 0;
+                self.samples_in_run = 0;
+                continue;
+            }
+            if budget == 0 {
+                break;
+            }
+            self.lattice.sweep();
+            self.sweeps_in_run += 1;
+            budget -= 1;
+        }
+        self.is_done()
+    }
+
+    /// The samples taken so far, of dim (params.n_runs, samples taken in
+    /// each run)
+    pub fn into_samples(self) -> ObservableSamples {
+        ObservableSamples {
+            names: self.names,
+            values: self.values,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::monte_carlo_measurement::MonteCarlo;
+    use crate::observable;
+
+    fn params() -> MonteCarloParams {
+        MonteCarloParams {
+            n_runs: 3,
+            sweeps_to_skip: 5,
+            samples_per_run: 4,
+            sweeps_between_samples: 2,
+            max_threads: None,
+            start_from_current: false,
+        }
+    }
+
+    fn lattice() -> Lattice2d {
+        let mut lattice = Lattice2d::new_basic([6, 6]);
+        lattice.seed(42);
+        lattice
+    }
+
+    #[test]
+    fn test_resume_matches_uninterrupted() {
+        let observables: [&dyn Observable<Lattice2d>; 2] = [&observable::Energy, &observable::Magnetization];
+        let expected = lattice().sample_all_metrics(&params(), &observables);
+
+        let mut sampler = ResumableSampler::new(lattice(), params(), &observables);
+        assert_eq!(sampler.total_sweeps(), 39);
+        let mut pieces = 0;
+        while !sampler.advance(7, &observables) {
+            pieces += 1;
+            assert_eq!(sampler.sweeps_done(), 7 * pieces);
+        }
+        assert_eq!(sampler.sweeps_done(), sampler.total_sweeps());
+        let samples = sampler.into_samples();
+        assert_eq!(samples.names, expected.names);
+        assert_eq!(samples.values, expected.values);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let observables: [&dyn Observable<Lattice2d>; 2] = [&observable::Energy, &observable::Magnetization];
+        // couplings that serde_json only parses back bit for bit
+        // with its float_roundtrip feature
+        let (beta, h) = (0.43180878744635387, 0.18583475263847138);
+        let lattice = || {
+            let mut lattice = lattice();
+            lattice.beta = beta;
+            lattice.h = h;
+            lattice
+        };
+        let mut uninterrupted = ResumableSampler::new(lattice(), params(), &observables);
+        uninterrupted.advance(usize::MAX, &observables);
+
+        let mut sampler = ResumableSampler::new(lattice(), params(), &observables);
+        sampler.advance(20, &observables);
+        let checkpoint = serde_json::to_string(&sampler).unwrap();
+        drop(sampler);
+        let mut resumed: ResumableSampler = serde_json::from_str(&checkpoint).unwrap();
+        assert_eq!(resumed.lattice.beta.to_bits(), f64::to_bits(beta));
+        assert_eq!(resumed.lattice.h.to_bits(), f64::to_bits(h));
+        resumed.advance(usize::MAX, &observables);
+        assert_eq!(resumed.lattice.nodes, uninterrupted.lattice.nodes);
+        let (resumed, uninterrupted) = (resumed.into_samples(), uninterrupted.into_samples());
+        assert_eq!(resumed.names, uninterrupted.names);
+        assert_eq!(resumed.values, uninterrupted.values);
+    }
+}
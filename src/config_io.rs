@@ -0,0 +1,535 @@
+//! Reading and writing spin configurations, to start from prepared
+//! states (domain walls, droplets) or to post-process snapshots with
+//! other tools. Lattice configurations (`Lattice2d::nodes`, loaded back
+//! with `Lattice2d::set_nodes`) can be stored as
+//!
+//! - plain text matrices, one row of the lattice per line
+//! - NumPy `.npy` arrays (`numpy.load` gives an int32 matrix)
+//! - PBM and PGM images, up spins being black (like the `#` of
+//!   `Lattice2d::disp_terminal`) and down spins white
+//!
+//! Graph configurations are stored as a text file of spins, one per
+//! line, alongside an edge list of `i j weight` lines.
+//!
+//! All readers return an `io::Error` of kind `InvalidData` for files
+//! they can't parse or that hold anything other than spins ±1.
+
+use ndarray::prelude::*;
+use std::io::{self, BufRead, Read, Write};
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Checks that a value is a spin
+fn spin(value: i64) -> io::Result<i32> {
+    match value {
+        -1 | 1 => Ok(value as i32),
+        _ => Err(invalid(format!("expected a spin -1 or 1, found {}", value))),
+    }
+}
+
+/// Writes the spins as a text matrix, space separated, one row per line
+pub fn write_text<W: Write>(nodes: &Array2<i32>, mut writer: W) -> io::Result<()> {
+    for row in nodes.rows() {
+        let line: Vec<String> = row.iter().map(|s| s.to_string()).collect();
+        writeln!(writer, "{}", line.join(" "))?;
+    }
+    Ok(())
+}
+
+/// Reads a text matrix of spins, separated by whitespace (or commas),
+/// skipping empty lines and lines starting with `#`
+pub fn read_text<R: BufRead>(reader: R) -> io::Result<Array2<i32>> {
+    let mut spins = vec![];
+    let mut width = None;
+    let mut height = 0;
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let row = line.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|t| !t.is_empty())
+            .map(|t| t.parse::<i64>().map_err(|e| invalid(format!("bad spin {:?}: {}", t, e))).and_then(spin))
+            .collect::<io::Result<Vec<i32>>>()?;
+        if *width.get_or_insert(row.len()) != row.len() {
+            return Err(invalid(format!("row {} has {} spins, expected {}", height, row.len(), width.unwrap())));
+        }
+        spins.extend(row);
+        height += 1;
+    }
+    Array2::from_shape_vec([height, width.unwrap_or(0)], spins).map_err(|e| invalid(e.to_string()))
+}
+
+/// Writes the spins as a version 1.0 `.npy` file of little endian int32
+pub fn write_npy<W: Write>(nodes: &Array2<i32>, mut writer: W) -> io::Result<()> {
+    let (height, width) = nodes.dim();
+    let mut header = format!(
+        "{{'descr': '<i4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        height, width
+    );
+    // magic (6) + version (2) + header length (2) + header + '\n' must
+    // be a multiple of 64
+    let padding = 63 - (10 + header.len()) % 64;
+    header.extend(std::iter::repeat_n(' ', padding));
+    header.push('\n');
+    writer.write_all(b"\x93NUMPY\x01\x00")?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    for &s in nodes.iter() {
+        writer.write_all(&s.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Returns the text following `'key':` in an npy header
+fn npy_field<'a>(header: &'a str, key: &str) -> io::Result<&'a str> {
+    let pattern = format!("'{}':", key);
+    let start = header.find(&pattern).ok_or_else(|| invalid(format!("npy header has no {}", key)))?;
+    Ok(header[start + pattern.len()..].trim_start())
+}
+
+/// Reads a 2d `.npy` array of spins
+///
+/// Accepts integer arrays of any width (values ±1), float arrays (values
+/// ±1.0) and boolean arrays (true for up), in C or Fortran order.
+pub fn read_npy<R: Read>(mut reader: R) -> io::Result<Array2<i32>> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic[..6] != b"\x93NUMPY" {
+        return Err(invalid("not an npy file".to_owned()));
+    }
+    let header_len = match magic[6] {
+        1 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_le_bytes(len) as usize
+        }
+        2 | 3 => {
+            let mut len = [0u8; 4];
+            reader.read_exact(&mut len)?;
+            u32::from_le_bytes(len) as usize
+        }
+        v => return Err(invalid(format!("unsupported npy version {}", v))),
+    };
+    let mut header = vec![0u8; header_len];
+    reader.read_exact(&mut header)?;
+    let header = String::from_utf8(header).map_err(|e| invalid(e.to_string()))?;
+
+    let descr = npy_field(&header, "descr")?;
+    let descr = descr.trim_start_matches('\'').split('\'').next().unwrap_or("");
+    let fortran_order = npy_field(&header, "fortran_order")?.starts_with("True");
+    let shape = npy_field(&header, "shape")?;
+    let shape: Vec<usize> = shape.strip_prefix('(')
+        .and_then(|s| s.split_once(')'))
+        .ok_or_else(|| invalid(format!("bad npy shape {:?}", shape)))?
+        .0
+        .split(',')
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.parse().map_err(|_| invalid(format!("bad npy shape {:?}", t))))
+        .collect::<io::Result<_>>()?;
+    if shape.len() != 2 {
+        return Err(invalid(format!("expected a 2d array, found shape {:?}", shape)));
+    }
+
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+    let (little_endian, kind, size) = match descr.as_bytes() {
+        [order, kind, size @ ..] => (
+            *order != b'>',
+            *kind,
+            std::str::from_utf8(size).ok().and_then(|s| s.parse::<usize>().ok()).unwrap_or(0),
+        ),
+        _ => (true, 0, 0),
+    };
+    if size == 0 || size > 8 {
+        return Err(invalid(format!("unsupported npy dtype {:?}", descr)));
+    }
+    let n = shape[0].checked_mul(shape[1]);
+    let n_bytes = n.and_then(|n| n.checked_mul(size));
+    let (Some(n), Some(n_bytes)) = (n, n_bytes) else {
+        return Err(invalid(format!("npy shape {:?} is too large", shape)));
+    };
+    if data.len() < n_bytes {
+        return Err(invalid(format!("npy data too short for dtype {:?}", descr)));
+    }
+    let values = data.chunks_exact(size)
+        .take(n)
+        .map(|bytes| {
+            let mut buf = [0u8; 8];
+            if little_endian {
+                buf[..size].copy_from_slice(bytes);
+            } else {
+                buf[..size].iter_mut().zip(bytes.iter().rev()).for_each(|(b, &x)| *b = x);
+            }
+            let raw = u64::from_le_bytes(buf);
+            match (kind, size) {
+                (b'b', 1) => Ok(if raw != 0 { 1 } else { -1 }),
+                (b'i', _) => {
+                    // sign extend
+                    let shift = 64 - 8 * size as u32;
+                    spin(((raw << shift) as i64) >> shift)
+                }
+                (b'u', _) => spin(raw as i64),
+                (b'f', 4) => spin_from_float(f32::from_bits(raw as u32) as f64),
+                (b'f', 8) => spin_from_float(f64::from_bits(raw)),
+                _ => Err(invalid(format!("unsupported npy dtype {:?}", descr))),
+            }
+        })
+        .collect::<io::Result<Vec<i32>>>()?;
+    let nodes = if fortran_order {
+        Array2::from_shape_vec([shape[1], shape[0]], values).map(|a| a.reversed_axes().as_standard_layout().to_owned())
+    } else {
+        Array2::from_shape_vec([shape[0], shape[1]], values)
+    };
+    nodes.map_err(|e| invalid(e.to_string()))
+}
+
+fn spin_from_float(value: f64) -> io::Result<i32> {
+    if value == 1.0 || value == -1.0 {
+        Ok(value as i32)
+    } else {
+        Err(invalid(format!("expected a spin -1 or 1, found {}", value)))
+    }
+}
+
+/// Writes the spins as a binary (P4) PBM image, up spins black
+pub fn write_pbm<W: Write>(nodes: &Array2<i32>, mut writer: W) -> io::Result<()> {
+    let (height, width) = nodes.dim();
+    write!(writer, "P4\n{} {}\n", width, height)?;
+    for row in nodes.rows() {
+        let mut bytes = vec![0u8; width.div_ceil(8)];
+        for (idx, &s) in row.iter().enumerate() {
+            if s == 1 {
+                bytes[idx / 8] |= 0x80 >> (idx % 8);
+            }
+        }
+        writer.write_all(&bytes)?;
+    }
+    Ok(())
+}
+
+/// Writes the spins as a binary (P5) PGM image, up spins black
+pub fn write_pgm<W: Write>(nodes: &Array2<i32>, mut writer: W) -> io::Result<()> {
+    let (height, width) = nodes.dim();
+    write!(writer, "P5\n{} {}\n255\n", width, height)?;
+    let pixels: Vec<u8> = nodes.iter().map(|&s| if s == 1 { 0 } else { 255 }).collect();
+    writer.write_all(&pixels)
+}
+
+/// Reads a PBM or PGM image, plain (P1, P2) or binary (P4, P5)
+///
+/// In a PBM black pixels are up spins, in a PGM pixels darker than
+/// half the maximum value are.
+pub fn read_pnm<R: Read>(mut reader: R) -> io::Result<Array2<i32>> {
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+    let mut pos = 0;
+
+    // header tokens are separated by whitespace, with comments from # to
+    // the end of the line
+    let next_token = |pos: &mut usize| -> io::Result<String> {
+        loop {
+            match data.get(*pos) {
+                Some(b'#') => {
+                    while data.get(*pos).is_some_and(|&c| c != b'\n') {
+                        *pos += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_whitespace() => *pos += 1,
+                Some(_) => break,
+                None => return Err(invalid("unexpected end of image".to_owned())),
+            }
+        }
+        let start = *pos;
+        while data.get(*pos).is_some_and(|c| !c.is_ascii_whitespace() && *c != b'#') {
+            *pos += 1;
+        }
+        Ok(String::from_utf8_lossy(&data[start..*pos]).into_owned())
+    };
+    let number = |token: String| -> io::Result<usize> {
+        token.parse().map_err(|_| invalid(format!("bad number {:?} in image header", token)))
+    };
+
+    let magic = next_token(&mut pos)?;
+    let width = number(next_token(&mut pos)?)?;
+    let height = number(next_token(&mut pos)?)?;
+    let maxval = match magic.as_str() {
+        "P2" | "P5" => number(next_token(&mut pos)?)?,
+        _ => 1,
+    };
+    let n = width.checked_mul(height)
+        .ok_or_else(|| invalid(format!("image size {}x{} is too large", width, height)))?;
+    // every format takes at least a bit per pixel, so nothing is
+    // allocated for a header promising more pixels than the data holds
+    if n / 8 > data.len() - pos {
+        return Err(invalid(format!("image data too short for {}x{} pixels", width, height)));
+    }
+    let dark = |value: usize| if 2 * value < maxval + 1 { 1 } else { -1 };
+
+    let spins: Vec<i32> = match magic.as_str() {
+        "P1" => {
+            // the digits of a plain PBM need not be separated
+            let mut spins = Vec::with_capacity(n);
+            let mut in_comment = false;
+            for &c in data[pos..].iter() {
+                match c {
+                    b'\n' => in_comment = false,
+                    _ if in_comment => (),
+                    b'#' => in_comment = true,
+                    b'0' => spins.push(-1),
+                    b'1' => spins.push(1),
+                    _ if c.is_ascii_whitespace() => (),
+                    _ => return Err(invalid(format!("bad PBM pixel {:?}", c as char))),
+                }
+                if spins.len() == n {
+                    break;
+                }
+            }
+            spins
+        }
+        "P2" => (0..n).map(|_| next_token(&mut pos).and_then(number).map(dark)).collect::<io::Result<_>>()?,
+        "P4" | "P5" => {
+            // a single whitespace character separates the header from the raster
+            let raster = &data[(pos + 1).min(data.len())..];
+            if magic == "P4" {
+                let row_bytes = width.div_ceil(8);
+                if raster.len() < row_bytes * height {
+                    return Err(invalid("PBM raster too short".to_owned()));
+                }
+                (0..n)
+                    .map(|i| {
+                        let (row, col) = (i / width, i % width);
+                        if raster[row * row_bytes + col / 8] & (0x80 >> (col % 8)) != 0 { 1 } else { -1 }
+                    })
+                    .collect()
+            } else {
+                let bytes_per_pixel = if maxval < 256 { 1 } else { 2 };
+                if n.checked_mul(bytes_per_pixel).is_none_or(|len| raster.len() < len) {
+                    return Err(invalid("PGM raster too short".to_owned()));
+                }
+                raster.chunks_exact(bytes_per_pixel)
+                    .take(n)
+                    .map(|b| dark(b.iter().fold(0, |v, &x| (v << 8) | x as usize)))
+                    .collect()
+            }
+        }
+        _ => return Err(invalid(format!("unsupported image type {:?}", magic))),
+    };
+    Array2::from_shape_vec([height, width], spins).map_err(|e| invalid(e.to_string()))
+}
+
+/// Writes the spins of a graph as text, one per line
+pub fn write_graph_spins<W: Write>(nodes: &Array1<i32>, mut writer: W) -> io::Result<()> {
+    for s in nodes.iter() {
+        writeln!(writer, "{}", s)?;
+    }
+    Ok(())
+}
+
+/// Reads the spins of a graph, separated by whitespace, skipping lines
+/// starting with `#`
+pub fn read_graph_spins<R: BufRead>(reader: R) -> io::Result<Array1<i32>> {
+    let mut spins = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        for token in line.split_whitespace() {
+            let value = token.parse::<i64>().map_err(|e| invalid(format!("bad spin {:?}: {}", token, e)))?;
+            spins.push(spin(value)?);
+        }
+    }
+    Ok(Array1::from(spins))
+}
+
+/// Writes the nonzero entries of an edge matrix as `i j weight` lines
+pub fn write_edge_list<W: Write>(edges: &Array2<f64>, mut writer: W) -> io::Result<()> {
+    for ((i, j), &w) in edges.indexed_iter() {
+        if w != 0.0 {
+            writeln!(writer, "{} {} {}", i, j, w)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads an edge list of `i j` or `i j weight` lines (weight 1 if
+/// missing) into an n_sites x n_sites edge matrix, skipping lines
+/// starting with `#`
+pub fn read_edge_list<R: BufRead>(reader: R, n_sites: usize) -> io::Result<Array2<f64>> {
+    let mut edges = Array2::zeros([n_sites, n_sites]);
+    for (line_no, line) in reader.lines().enumerate() {
+        let line = line?;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() || tokens[0].starts_with('#') {
+            continue;
+        }
+        let bad_line = || invalid(format!("bad edge on line {}: {:?}", line_no + 1, line));
+        if tokens.len() < 2 || tokens.len() > 3 {
+            return Err(bad_line());
+        }
+        let i: usize = tokens[0].parse().map_err(|_| bad_line())?;
+        let j: usize = tokens[1].parse().map_err(|_| bad_line())?;
+        let w: f64 = tokens.get(2).map_or(Ok(1.0), |t| t.parse()).map_err(|_| bad_line())?;
+        if i >= n_sites || j >= n_sites {
+            return Err(invalid(format!("edge {} {} out of range for {} sites", i, j, n_sites)));
+        }
+        edges[[i, j]] = w;
+    }
+    Ok(edges)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lattice2d::Lattice2d;
+
+    fn nodes() -> Array2<i32> {
+        Lattice2d::new_basic([5, 11]).nodes
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        let nodes = nodes();
+        let mut buf = vec![];
+        write_text(&nodes, &mut buf).unwrap();
+        assert_eq!(read_text(&buf[..]).unwrap(), nodes);
+        let parsed = read_text("# a comment\n1, -1\n\n-1 1\n".as_bytes()).unwrap();
+        assert_eq!(parsed, array![[1, -1], [-1, 1]]);
+        assert!(read_text("1 -1\n1\n".as_bytes()).is_err());
+        assert!(read_text("1 0\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_npy_round_trip() {
+        let nodes = nodes();
+        let mut buf = vec![];
+        write_npy(&nodes, &mut buf).unwrap();
+        let header_len = u16::from_le_bytes([buf[8], buf[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        assert_eq!(buf.len(), 10 + header_len + 4 * 55);
+        assert_eq!(read_npy(&buf[..]).unwrap(), nodes);
+    }
+
+    #[test]
+    fn test_read_npy_dtypes() {
+        fn npy(descr: &str, fortran: bool, data: &[u8]) -> Vec<u8> {
+            let header = format!(
+                "{{'descr': '{}', 'fortran_order': {}, 'shape': (2, 3), }}\n",
+                descr,
+                if fortran { "True" } else { "False" }
+            );
+            let mut buf = b"\x93NUMPY\x01\x00".to_vec();
+            buf.extend((header.len() as u16).to_le_bytes());
+            buf.extend(header.as_bytes());
+            buf.extend(data);
+            buf
+        }
+        let expected = array![[1, -1, 1], [-1, -1, 1]];
+        let int8 = npy("|i1", false, &[1, 255, 1, 255, 255, 1]);
+        assert_eq!(read_npy(&int8[..]).unwrap(), expected);
+        let int64: Vec<u8> = [1i64, -1, 1, -1, -1, 1].iter().flat_map(|x| x.to_le_bytes()).collect();
+        assert_eq!(read_npy(&npy("<i8", false, &int64)[..]).unwrap(), expected);
+        // Fortran order stores the columns one after the other
+        let float: Vec<u8> = [1.0f64, -1.0, -1.0, -1.0, 1.0, 1.0].iter().flat_map(|x| x.to_le_bytes()).collect();
+        assert_eq!(read_npy(&npy("<f8", true, &float)[..]).unwrap(), expected);
+        let boolean = npy("|b1", false, &[1, 0, 1, 0, 0, 1]);
+        assert_eq!(read_npy(&boolean[..]).unwrap(), expected);
+        assert!(read_npy(&npy("<i4", false, &[0; 24])[..]).is_err());
+    }
+
+    #[test]
+    fn test_read_npy_malformed_header() {
+        fn npy(header: &str) -> Vec<u8> {
+            let mut buf = b"\x93NUMPY\x01\x00".to_vec();
+            buf.extend((header.len() as u16).to_le_bytes());
+            buf.extend(header.as_bytes());
+            buf.extend([1u8; 8]);
+            buf
+        }
+        for header in [
+            "{'descr': '|i1', 'fortran_order': False, 'shape': ",
+            "{'descr': '|i1', 'fortran_order': False, 'shape': )",
+            "{'descr': '|i1', 'fortran_order': False, 'shape': (2, 4",
+            "{'descr': '|i1', 'fortran_order': False, 'shape': (2, x), }",
+            "{'descr': '<i8', 'fortran_order': False, 'shape': (4294967296, 4294967296), }",
+            "{'descr': '<i8', 'fortran_order': False, 'shape': (2305843009213693952, 1), }",
+        ] {
+            let err = read_npy(&npy(header)[..]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", header);
+        }
+        let ok = npy("{'descr': '|i1', 'fortran_order': False, 'shape': (2, 4), }");
+        assert_eq!(read_npy(&ok[..]).unwrap().dim(), (2, 4));
+    }
+
+    #[test]
+    fn test_pnm_round_trip() {
+        let nodes = nodes();
+        let mut pbm = vec![];
+        write_pbm(&nodes, &mut pbm).unwrap();
+        assert_eq!(read_pnm(&pbm[..]).unwrap(), nodes);
+        let mut pgm = vec![];
+        write_pgm(&nodes, &mut pgm).unwrap();
+        assert_eq!(read_pnm(&pgm[..]).unwrap(), nodes);
+    }
+
+    #[test]
+    fn test_read_plain_pnm() {
+        let expected = array![[1, -1, -1], [-1, 1, 1]];
+        let pbm = "P1\n# made by hand\n3 2\n1 0 0\n011\n";
+        assert_eq!(read_pnm(pbm.as_bytes()).unwrap(), expected);
+        let pgm = "P2 3 2 15\n0 15 12\n9 3 7\n";
+        assert_eq!(read_pnm(pgm.as_bytes()).unwrap(), expected);
+        assert!(read_pnm("P3 1 1 255 0 0 0".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_read_pnm_malformed_header() {
+        for header in [
+            "P4 3",
+            "P4 3 x\n",
+            "P1 4294967296 4294967296\n1",
+            "P1 1000000000 1000000000\n1",
+            "P2 1000000 1000000 255\n0",
+            "P5 1000000000000 1000000000 65535\n",
+            "P5 2 2 65535\n\x00\x00\x00\x00",
+        ] {
+            let err = read_pnm(header.as_bytes()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", header);
+        }
+    }
+
+    #[test]
+    fn test_graph_io() {
+        let nodes = array![1, -1, -1, 1];
+        let mut buf = vec![];
+        write_graph_spins(&nodes, &mut buf).unwrap();
+        assert_eq!(read_graph_spins(&buf[..]).unwrap(), nodes);
+
+        let edges = array![[0.0, 1.0, 0.0], [0.5, 0.0, 0.0], [0.0, 0.0, 2.0]];
+        let mut buf = vec![];
+        write_edge_list(&edges, &mut buf).unwrap();
+        assert_eq!(String::from_utf8(buf.clone()).unwrap(), "0 1 1\n1 0 0.5\n2 2 2\n");
+        assert_eq!(read_edge_list(&buf[..], 3).unwrap(), edges);
+        let unweighted = read_edge_list("# i j\n0 1\n".as_bytes(), 2).unwrap();
+        assert_eq!(unweighted, array![[0.0, 1.0], [0.0, 0.0]]);
+        assert!(read_edge_list("0 5\n".as_bytes(), 2).is_err());
+    }
+
+    #[test]
+    fn test_set_nodes() {
+        let mut lattice = Lattice2d::new_basic([4, 4]);
+        lattice.set_tracking(true);
+        lattice.set_nodes(read_text("1 1 1\n1 1 1\n".as_bytes()).unwrap());
+        assert_eq!(lattice.dims, [2, 3]);
+        assert_eq!(lattice.n_sites, 6);
+        assert_eq!(lattice.tracked_spin_sum(), Some(6));
+        // an empty file gives a 0 x 0 array, not a lattice
+        assert!(lattice.try_set_nodes(read_text("".as_bytes()).unwrap()).is_err());
+        assert_eq!(lattice.dims, [2, 3]);
+    }
+}
@@ -0,0 +1,63 @@
+//! The error type of the crate. Functions that can fail on bad input
+//! or in a worker thread return an `IsingError` instead of panicking,
+//! so long-running programs built on the crate can report the problem
+//! and carry on. Most of them have a panicking counterpart (e.g.
+//! `Lattice2d::sweep` for `Lattice2d::try_sweep`) for scripts and tests.
+
+use crate::lattice2d::ParseLatticeError;
+use std::error::Error;
+use std::fmt;
+
+/// Errors returned by the fallible functions of the crate
+#[derive(Clone, Debug, PartialEq)]
+pub enum IsingError {
+    InvalidDims([usize; 2]),         // a side is 0, odd with checkerboard sweeps, or not fitting a packed lattice
+    InvalidTemperature(f64),         // a temperature that is not positive, or a negative beta
+    NonFinite(&'static str, f64),    // a parameter (j, h, beta or the temperature) is NaN or infinite
+    InvalidProbability(f64),         // a probability outside of [0, 1]
+    InvalidSpin(i32),                // a spin other than -1 or 1
+    TooFewSamples(usize),            // fewer than two samples to estimate a standard error from
+    Unimplemented(&'static str),     // a feature that is not implemented yet, e.g. Glauber updates
+    Parse(ParseLatticeError),        // a lattice could not be parsed from text
+    ThreadPool(String),              // the worker pool could not be built
+    WorkerPanicked(String),          // a run panicked, with the panic message
+}
+
+impl fmt::Display for IsingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IsingError::InvalidDims(dims) => write!(
+                f,
+                "invalid lattice dims {:?}, sides must be positive (and even for checkerboard sweeps, \
+                 with a multiple of 64 columns for packed lattices)",
+                dims
+            ),
+            IsingError::InvalidTemperature(t) => write!(f, "invalid temperature {}, it must be positive", t),
+            IsingError::NonFinite(name, value) => write!(f, "{} must be finite, got {}", name, value),
+            IsingError::InvalidProbability(p) => write!(f, "invalid probability {}, it must be in [0, 1]", p),
+            IsingError::InvalidSpin(s) => write!(f, "invalid spin {}, Ising spins are -1 or 1", s),
+            IsingError::TooFewSamples(n) => {
+                write!(f, "{} samples are too few for a standard error, at least 2 are needed", n)
+            }
+            IsingError::Unimplemented(what) => write!(f, "{} not yet implemented", what),
+            IsingError::Parse(e) => write!(f, "{}", e),
+            IsingError::ThreadPool(msg) => write!(f, "could not build the thread pool: {}", msg),
+            IsingError::WorkerPanicked(msg) => write!(f, "a Monte Carlo run panicked: {}", msg),
+        }
+    }
+}
+
+impl Error for IsingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            IsingError::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseLatticeError> for IsingError {
+    fn from(e: ParseLatticeError) -> Self {
+        IsingError::Parse(e)
+    }
+}
@@ -0,0 +1,123 @@
+//! Exact results for small periodic lattices, for checking the
+//! samplers against. The density of states is counted with a transfer
+//! matrix over the rows of the lattice, and agrees with the one
+//! tabulated by Beale (Phys. Rev. Lett. 76, 78, 1996).
+
+use crate::wang_landau::DensityOfStates;
+
+/// Counts the states of a periodic lattice (at h = 0) for every bond
+/// sum b = ∑ s_i s_j over neighbouring pairs. Returns the (b, count)
+/// pairs with a nonzero count, sorted by b.
+///
+/// The cost grows as 8^L with the shorter side L (first row × last
+/// row × next row configurations), so keep it at 6 or so. The counts
+/// are exact up to 126 sites.
+pub fn count_states(dims: [usize; 2]) -> Vec<(i32, u128)> {
+    // the lattice is symmetric under transposition, so the rows can be
+    // taken along the shorter side
+    let (n_rows, width) = (dims[0].max(dims[1]), dims[0].min(dims[1]));
+    let n_sites = n_rows * width;
+    assert!(width > 0, "Dimensions must be positive");
+    assert!(n_sites <= 126, "Too many sites to count exactly");
+    let n_configs = 1usize << width;
+    let offset = 2 * n_sites; // b ranges from -2N to 2N
+    let n_levels = 4 * n_sites + 1;
+
+    // ∑ s_k s_l over the bonds within a row, or between two rows
+    let aligned = |x: usize| width as i32 - 2 * x.count_ones() as i32;
+    let rotate = |row: usize| ((row << 1) | (row >> (width - 1))) & (n_configs - 1);
+    let row_bonds: Vec<i32> = (0..n_configs).map(|row| aligned(row ^ rotate(row))).collect();
+
+    let mut counts = vec![0u128; n_levels];
+    for first in 0..n_configs {
+        // counts[row][b] over the configurations of the rows so far,
+        // with the given first and last row
+        let mut partial = vec![vec![0u128; n_levels]; n_configs];
+        partial[first][(offset as i32 + row_bonds[first]) as usize] = 1;
+        for _ in 1..n_rows {
+            let mut next = vec![vec![0u128; n_levels]; n_configs];
+            for (last, counts_last) in partial.iter().enumerate() {
+                for (row, counts_row) in next.iter_mut().enumerate() {
+                    let shift = row_bonds[row] + aligned(last ^ row);
+                    add_shifted(counts_row, counts_last, shift);
+                }
+            }
+            partial = next;
+        }
+        // close the lattice with the bonds from the last row to the first
+        for (last, counts_last) in partial.iter().enumerate() {
+            add_shifted(&mut counts, counts_last, aligned(last ^ first));
+        }
+    }
+
+    counts.into_iter()
+        .enumerate()
+        .filter(|&(_, count)| count > 0)
+        .map(|(idx, count)| (idx as i32 - offset as i32, count))
+        .collect()
+}
+
+/// target[i + shift] += source[i]
+fn add_shifted(target: &mut [u128], source: &[u128], shift: i32) {
+    for (idx, &count) in source.iter().enumerate() {
+        if count > 0 {
+            target[(idx as i32 + shift) as usize] += count;
+        }
+    }
+}
+
+/// Exact density of states of a periodic lattice with coupling j, over
+/// the levels E = -j ∑ s_i s_j (h = 0)
+pub fn density_of_states(dims: [usize; 2], j: f64) -> DensityOfStates {
+    let (energies, ln_g): (Vec<f64>, Vec<f64>) = count_states(dims)
+        .into_iter()
+        .map(|(bond_sum, count)| (-j * bond_sum as f64, (count as f64).ln()))
+        .unzip();
+    let n_sites = dims[0] * dims[1];
+    DensityOfStates::new(energies, ln_g, n_sites as f64 * 2f64.ln())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lattice2d::Lattice2d;
+    use crate::measurement::Measurement;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_count_states_beale() {
+        // g(E) of the 4x4 lattice, as tabulated by Beale
+        let beale = vec![
+            (-32, 2), (-24, 32), (-20, 64), (-16, 424), (-12, 1728), (-8, 6688), (-4, 13568),
+            (0, 20524),
+            (4, 13568), (8, 6688), (12, 1728), (16, 424), (20, 64), (24, 32), (32, 2),
+        ];
+        let counts: Vec<(i32, u128)> = count_states([4, 4]).into_iter().map(|(b, c)| (-b, c)).rev().collect();
+        assert_eq!(counts, beale);
+    }
+
+    #[test]
+    fn test_count_states_enumeration() {
+        // compare with a brute force enumeration of all 2^12 states
+        let dims = [3, 4];
+        let mut lattice = Lattice2d::new_basic(dims);
+        let mut brute_force = BTreeMap::new();
+        for config in 0..1usize << 12 {
+            lattice.nodes = ndarray::Array2::from_shape_fn(dims, |(i, j)| {
+                if config >> (i * dims[1] + j) & 1 == 1 { 1 } else { -1 }
+            });
+            // the dot product counts every pair from both ends
+            *brute_force.entry(lattice.get_dot_spin_neighbours() / 2).or_insert(0u128) += 1;
+        }
+        assert_eq!(count_states(dims), brute_force.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_density_of_states() {
+        let dos = density_of_states([4, 4], 1.0);
+        assert_eq!(dos.energies.first(), Some(&-32.0));
+        assert!((dos.ln_g[0] - 2f64.ln()).abs() < 1e-12);
+        // high temperature limit
+        assert!((dos.entropy(0.0) - 16.0 * 2f64.ln()).abs() < 1e-9);
+    }
+}
@@ -1,5 +1,6 @@
 //! Graph spin 1/2 Type
 
+use crate::error::IsingError;
 use ndarray::prelude::*;
 use rand::prelude::SliceRandom;
 use rand::Rng;
@@ -28,7 +29,6 @@ pub enum EdgeType {
 
 /// A type encapsulating an Ising model on
 /// a graph and basic operations performed on it
-
 pub struct Graph {
     pub n_sites: u32,       // = nodes.len()
     pub nodes: Array1<i32>, // An array of nodes
@@ -45,12 +45,18 @@ pub struct Graph {
 /// Implement basic methods on Graph type
 impl Graph {
     /// Create a new Graph of given size with random edges
+    ///
+    /// Panics if prob is not in [0, 1], see `try_new_basic`.
     pub fn new_basic(n_sites: u32, prob: f64) -> Self {
-        assert!(prob >= 0.0 && prob <= 1.0);
-        Self::new(
+        Self::try_new_basic(n_sites, prob).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `new_basic`, but returns an error if prob is not in [0, 1]
+    pub fn try_new_basic(n_sites: u32, prob: f64) -> Result<Self, IsingError> {
+        Self::try_new(
             n_sites,
             UpdateRule::Metropolis,
-            EdgeType::BinaryRandom { prob: prob },
+            EdgeType::BinaryRandom { prob },
             SpinType::SpinHalf,
             InitType::Random,
             1.0f64,
@@ -59,6 +65,8 @@ impl Graph {
         )
     }
 
+    /// Panics for invalid parameters, see `try_new`
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         n_sites: u32,
         update_rule: UpdateRule,
@@ -69,40 +77,54 @@ impl Graph {
         h: f64,
         beta: f64,
     ) -> Self {
+        Self::try_new(n_sites, update_rule, edge_type, spin_type, init_type, j, h, beta)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `new`, but returns an error if the edge probability is not in
+    /// [0, 1] or the edge type is not implemented yet
+    #[allow(clippy::too_many_arguments)]
+    pub fn try_new(
+        n_sites: u32,
+        update_rule: UpdateRule,
+        edge_type: EdgeType,
+        spin_type: SpinType,
+        init_type: InitType,
+        j: f64,
+        h: f64,
+        beta: f64,
+    ) -> Result<Self, IsingError> {
         // TODO: implement init for different spin types
-        let nodes: Array1<i32>;
         let mut rng = rand::thread_rng();
-        match init_type {
+        let nodes: Array1<i32> = match init_type {
             InitType::Random => {
-                nodes = Array::from_iter((0..n_sites).map(|_| *[-1, 1].choose(&mut rng).unwrap()));
+                Array::from_iter((0..n_sites).map(|_| *[-1, 1].choose(&mut rng).unwrap()))
             }
             InitType::AllUp => {
-                nodes = Array::from_iter((0..n_sites).map(|_| 1));
+                Array::from_iter((0..n_sites).map(|_| 1))
             }
-        }
+        };
         // TODO: implement init for different edge types
-        let edges; // : Array2<f64> = Array2<f64>::zeros((n_sites, n_sites));
-        match edge_type {
-            EdgeType::BinaryRandom { prob } => {
+        let edges = match edge_type {
+            EdgeType::BinaryRandom { prob } if (0.0..=1.0).contains(&prob) => {
                 // Probabilistically fill the edge matrix with ones with prob p, and zeros with prob 1-p
-                edges = Array2::from_shape_fn([n_sites as usize , n_sites as usize], |_| -> f64 {if rng.gen::<f64>() < prob {1.0} else {0.0}}); // *[0.0,1.0].choose(&mut rng, prob).unwrap());
-            }
-            _ => {
-                panic!("Not yet implemented edge type, try EdgeType::BinaryRandom instead")
+                Array2::from_shape_fn([n_sites as usize , n_sites as usize], |_| -> f64 {if rng.gen::<f64>() < prob {1.0} else {0.0}}) // *[0.0,1.0].choose(&mut rng, prob).unwrap());
             }
+            EdgeType::BinaryRandom { prob } => return Err(IsingError::InvalidProbability(prob)),
+            EdgeType::UnifRandom => return Err(IsingError::Unimplemented("EdgeType::UnifRandom")),
         };
-        Graph {
-            n_sites:n_sites,
-            nodes:nodes,
-            edges:edges,
-            update_rule:update_rule,
-            edge_type:edge_type,
-            spin_type:spin_type,
-            init_type:init_type,
-            j:j,
-            h:h,
-            beta:beta,
-        }
+        Ok(Graph {
+            n_sites,
+            nodes,
+            edges,
+            update_rule,
+            edge_type,
+            spin_type,
+            init_type,
+            j,
+            h,
+            beta,
+        })
     }
 }
 
@@ -123,4 +145,22 @@ mod tests {
             0.4f64,
         );
     }
+
+    #[test]
+    fn test_graph_errors() {
+        assert_eq!(Graph::try_new_basic(10, 1.5).err(), Some(IsingError::InvalidProbability(1.5)));
+        assert!(Graph::try_new_basic(10, f64::NAN).is_err());
+        let unif = Graph::try_new(
+            10u32,
+            UpdateRule::Metropolis,
+            EdgeType::UnifRandom,
+            SpinType::SpinHalf,
+            InitType::AllUp,
+            1.0f64,
+            0.0f64,
+            0.4f64,
+        );
+        assert!(matches!(unif, Err(IsingError::Unimplemented(_))));
+        assert!(Graph::try_new_basic(10, 0.0).unwrap().edges.iter().all(|&e| e == 0.0));
+    }
 }
@@ -0,0 +1,317 @@
+//! Snapshots and animations of a lattice, for watching a run without a
+//! terminal (e.g. on a headless server). A snapshot is an RGB image in
+//! which every site becomes a square of `scale` × `scale` pixels,
+//! coloured by a colour map. Snapshots can be written as PPM, and with
+//! the `image` feature as PNG. A run can be saved as numbered frames
+//! with `save_frames`, or with the `image` feature as an animated GIF
+//! with `GifRecorder`. Everything is pure Rust.
+//!
+//! Besides spins, any matrix of values in [-1, 1] can be drawn with
+//! `render_values`, e.g. the time averaged magnetization of every site.
+
+use crate::lattice2d::Lattice2d;
+use ndarray::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Maps values in [-1, 1] (-1 being a down spin, 1 an up spin) to colours
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColorMap {
+    Grayscale,                // up spins black, down spins white, like `config_io::write_pgm`
+    BlueRed,                  // down spins blue, up spins red, through white
+    Gradient(Vec<[u8; 3]>),   // evenly spaced colours from -1 to 1, interpolated linearly
+}
+
+impl ColorMap {
+    /// Colour of a value, values outside of [-1, 1] are clamped
+    pub fn color(&self, value: f64) -> [u8; 3] {
+        match self {
+            ColorMap::Grayscale => Self::interpolate(&[[255, 255, 255], [0, 0, 0]], value),
+            ColorMap::BlueRed => Self::interpolate(&[[0, 0, 255], [255, 255, 255], [255, 0, 0]], value),
+            ColorMap::Gradient(colors) => Self::interpolate(colors, value),
+        }
+    }
+
+    fn interpolate(colors: &[[u8; 3]], value: f64) -> [u8; 3] {
+        assert!(!colors.is_empty(), "A gradient needs at least one colour");
+        if colors.len() == 1 {
+            return colors[0];
+        }
+        let x = if value.is_nan() { 0.0 } else { value.clamp(-1.0, 1.0) };
+        let pos = (x + 1.0) / 2.0 * (colors.len() - 1) as f64;
+        let lo = (pos.floor() as usize).min(colors.len() - 2);
+        let t = pos - lo as f64;
+        let mut color = [0; 3];
+        for (c, (&a, &b)) in color.iter_mut().zip(colors[lo].iter().zip(colors[lo + 1].iter())) {
+            *c = (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        }
+        color
+    }
+}
+
+/// How a lattice is drawn
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageOptions {
+    pub color_map: ColorMap,
+    pub scale: usize, // side in pixels of the square drawn for every site
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        ImageOptions {
+            color_map: ColorMap::Grayscale,
+            scale: 1,
+        }
+    }
+}
+
+/// An 8 bit RGB image, rows top to bottom
+#[derive(Clone, Debug, PartialEq)]
+pub struct RgbImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>, // r, g, b of every pixel, of len 3 * width * height
+}
+
+/// Draws the spins of the lattice, row idx0 of the lattice being row
+/// idx0 of the image
+pub fn render_lattice(lattice: &Lattice2d, options: &ImageOptions) -> RgbImage {
+    render_values(&lattice.nodes.mapv(|s| s as f64), options)
+}
+
+/// Draws a matrix of values in [-1, 1]
+pub fn render_values(values: &Array2<f64>, options: &ImageOptions) -> RgbImage {
+    assert!(options.scale > 0, "The scale must be positive");
+    let scale = options.scale;
+    let (rows, cols) = values.dim();
+    let (width, height) = (cols * scale, rows * scale);
+    let mut pixels = Vec::with_capacity(3 * width * height);
+    for row in values.rows() {
+        let line: Vec<u8> = row.iter()
+            .flat_map(|&x| {
+                let color = options.color_map.color(x);
+                std::iter::repeat_n(color, scale).flatten()
+            })
+            .collect();
+        for _ in 0..scale {
+            pixels.extend_from_slice(&line);
+        }
+    }
+    RgbImage { width, height, pixels }
+}
+
+/// Writes the image as a binary PPM (P6)
+pub fn write_ppm<W: Write>(image: &RgbImage, mut writer: W) -> io::Result<()> {
+    write!(writer, "P6\n{} {}\n255\n", image.width, image.height)?;
+    writer.write_all(&image.pixels)
+}
+
+/// Writes the image as a PNG
+#[cfg(feature = "image")]
+pub fn write_png<W: Write>(image: &RgbImage, writer: W) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(writer, image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&image.pixels).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+/// File format of the frames written by `save_frames`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameFormat {
+    Ppm,
+    #[cfg(feature = "image")]
+    Png,
+}
+
+/// Saves n_frames snapshots of the lattice, sweeping it
+/// sweeps_between_frames times before each one, as numbered files
+/// `<prefix>00000.<ext>`, `<prefix>00001.<ext>`, … in the directory
+///
+/// Returns the paths of the frames. Tools like ffmpeg can turn them into
+/// a video.
+pub fn save_frames(
+    lattice: &mut Lattice2d,
+    dir: &Path,
+    prefix: &str,
+    n_frames: usize,
+    sweeps_between_frames: usize,
+    options: &ImageOptions,
+    format: FrameFormat,
+) -> io::Result<Vec<PathBuf>> {
+    let ext = match format {
+        FrameFormat::Ppm => "ppm",
+        #[cfg(feature = "image")]
+        FrameFormat::Png => "png",
+    };
+    (0..n_frames)
+        .map(|frame| {
+            lattice.sweep_n(sweeps_between_frames);
+            let image = render_lattice(lattice, options);
+            let path = dir.join(format!("{}{:05}.{}", prefix, frame, ext));
+            let mut writer = BufWriter::new(File::create(&path)?);
+            match format {
+                FrameFormat::Ppm => write_ppm(&image, &mut writer)?,
+                #[cfg(feature = "image")]
+                FrameFormat::Png => write_png(&image, &mut writer)?,
+            }
+            writer.flush()?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Writes frames of a lattice to an animated GIF, looping forever
+///
+/// The palette holds 256 colours of the colour map, so values between
+/// -1 and 1 are drawn to within 1/128.
+#[cfg(feature = "image")]
+pub struct GifRecorder<W: Write> {
+    encoder: gif::Encoder<W>,
+    options: ImageOptions,
+    delay: u16,
+}
+
+#[cfg(feature = "image")]
+impl<W: Write> GifRecorder<W> {
+    /// Starts a GIF of a lattice of the given dims, showing every frame
+    /// for delay hundredths of a second
+    pub fn new(writer: W, dims: [usize; 2], options: ImageOptions, delay: u16) -> io::Result<Self> {
+        assert!(options.scale > 0, "The scale must be positive");
+        let (width, height) = (dims[1] * options.scale, dims[0] * options.scale);
+        if width > u16::MAX as usize || height > u16::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "image too large for a GIF"));
+        }
+        let palette: Vec<u8> = (0..256)
+            .flat_map(|i| options.color_map.color(Self::value(i as u8)))
+            .collect();
+        let mut encoder = gif::Encoder::new(writer, width as u16, height as u16, &palette).map_err(io::Error::other)?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+        Ok(GifRecorder { encoder, options, delay })
+    }
+
+    /// The value drawn by palette entry i
+    fn value(i: u8) -> f64 {
+        i as f64 / 255.0 * 2.0 - 1.0
+    }
+
+    /// Adds a snapshot of the spins as a frame
+    pub fn add_frame(&mut self, lattice: &Lattice2d) -> io::Result<()> {
+        self.add_values(&lattice.nodes.mapv(|s| s as f64))
+    }
+
+    /// Adds a matrix of values in [-1, 1] as a frame
+    pub fn add_values(&mut self, values: &Array2<f64>) -> io::Result<()> {
+        let scale = self.options.scale;
+        let (rows, cols) = values.dim();
+        let mut indices = Vec::with_capacity(rows * cols * scale * scale);
+        for row in values.rows() {
+            let line: Vec<u8> = row.iter()
+                .flat_map(|&x| {
+                    let x = if x.is_nan() { 0.0 } else { x.clamp(-1.0, 1.0) };
+                    std::iter::repeat_n(((x + 1.0) / 2.0 * 255.0).round() as u8, scale)
+                })
+                .collect();
+            for _ in 0..scale {
+                indices.extend_from_slice(&line);
+            }
+        }
+        let frame = gif::Frame {
+            width: (cols * scale) as u16,
+            height: (rows * scale) as u16,
+            buffer: indices.into(),
+            delay: self.delay,
+            ..gif::Frame::default()
+        };
+        self.encoder.write_frame(&frame).map_err(io::Error::other)
+    }
+
+    /// Records n_frames frames of a run, sweeping the lattice
+    /// sweeps_between_frames times before each one
+    pub fn record(&mut self, lattice: &mut Lattice2d, n_frames: usize, sweeps_between_frames: usize) -> io::Result<()> {
+        for _ in 0..n_frames {
+            lattice.sweep_n(sweeps_between_frames);
+            self.add_frame(lattice)?;
+        }
+        Ok(())
+    }
+
+    /// Ends the GIF and returns the underlying writer
+    pub fn into_inner(self) -> io::Result<W> {
+        self.encoder.into_inner().map_err(io::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_color_maps() {
+        assert_eq!(ColorMap::Grayscale.color(1.0), [0, 0, 0]);
+        assert_eq!(ColorMap::Grayscale.color(-1.0), [255, 255, 255]);
+        assert_eq!(ColorMap::BlueRed.color(0.0), [255, 255, 255]);
+        assert_eq!(ColorMap::BlueRed.color(5.0), [255, 0, 0]);
+        let gradient = ColorMap::Gradient(vec![[0, 0, 0], [200, 100, 0]]);
+        assert_eq!(gradient.color(0.0), [100, 50, 0]);
+    }
+
+    #[test]
+    fn test_render_ppm() {
+        let mut lattice = Lattice2d::new_basic([2, 3]);
+        lattice.set_nodes(array![[1, -1, 1], [-1, -1, 1]]);
+        let options = ImageOptions {
+            color_map: ColorMap::Grayscale,
+            scale: 2,
+        };
+        let image = render_lattice(&lattice, &options);
+        assert_eq!((image.width, image.height), (6, 4));
+        // second pixel of the first row is still the first site,
+        // the third one is the second site
+        assert_eq!(&image.pixels[3..9], &[0, 0, 0, 255, 255, 255]);
+        // row 2 of the image is row 1 of the lattice
+        assert_eq!(&image.pixels[2 * 18..2 * 18 + 3], &[255, 255, 255]);
+
+        let mut ppm = vec![];
+        write_ppm(&image, &mut ppm).unwrap();
+        assert!(ppm.starts_with(b"P6\n6 4\n255\n"));
+        assert_eq!(ppm.len(), 11 + 3 * 24);
+    }
+
+    #[test]
+    fn test_save_frames() {
+        let dir = std::env::temp_dir().join(format!("ising_frames_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut lattice = Lattice2d::new_basic([4, 4]);
+        let paths = save_frames(&mut lattice, &dir, "frame_", 3, 1, &ImageOptions::default(), FrameFormat::Ppm).unwrap();
+        assert_eq!(paths.len(), 3);
+        assert!(paths[2].ends_with("frame_00002.ppm"));
+        assert_eq!(std::fs::read(&paths[2]).unwrap().len(), 11 + 3 * 16);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_png_and_gif() {
+        let mut lattice = Lattice2d::new_basic([4, 5]);
+        let mut png = vec![];
+        write_png(&render_lattice(&lattice, &ImageOptions::default()), &mut png).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+
+        let options = ImageOptions {
+            color_map: ColorMap::BlueRed,
+            scale: 3,
+        };
+        let mut recorder = GifRecorder::new(vec![], [4, 5], options, 5).unwrap();
+        recorder.record(&mut lattice, 4, 1).unwrap();
+        let gif = recorder.into_inner().unwrap();
+        assert!(gif.starts_with(b"GIF89a"));
+        // logical screen size, little endian
+        assert_eq!(&gif[6..10], &[15, 0, 12, 0]);
+    }
+}
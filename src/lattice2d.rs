@@ -3,12 +3,22 @@
 //! You can use it with the built-in measurement and monte-carlo types
 //! (refer to examples), or you can just use the lattice. 
 
+use crate::error::IsingError;
+use crate::measurement::Measurement;
 use ndarray::prelude::*;
 use rand::prelude::SliceRandom;
 use rand::Rng;
+use rand_pcg::Pcg64;
+use rayon::prelude::*;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 /// Update rule options for Lattice 2d (Metropolis only for now)
-#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug)]
 pub enum UpdateRule {
     Metropolis,
     Glauber,
@@ -20,13 +30,24 @@ pub enum UpdateRule {
 // - SpinThreeHalf
 // - XY
 /// Types of spin system (SpinHalf only for now)
-#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug)]
 pub enum SpinType {
     SpinHalf,
 }
 
+/// Order in which `Lattice2d::sweep` visits the sites
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SiteOrder {
+    Random,       // n_sites sites picked uniformly at random (with repetition)
+    Typewriter,   // every site in turn, row by row
+    Checkerboard, // every site of one sublattice, then the other (needs even dims)
+}
+
 /// Initial condition (Random or AllUp)
-#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug)]
 pub enum InitType {
     Random,
     AllUp,
@@ -40,6 +61,12 @@ pub enum InitType {
 /// neighbours
 ///
 /// The 2D lattice type
+///
+/// Cloning copies everything, spins and random number generator
+/// included, so a clone evolves exactly like the original; use
+/// `fresh_like` for a lattice with the same parameters and new spins.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone)]
 pub struct Lattice2d {
     pub dims: [usize; 2],
     pub n_sites: i32,       // the number of spin 1/2 sites == dims[0] * dims[1]
@@ -47,28 +74,241 @@ pub struct Lattice2d {
     update_rule: UpdateRule,
     pub spin_type: SpinType,
     pub init_type: InitType,
+    pub site_order: SiteOrder, // order of the sites in a sweep, defaults to Random
     pub j: f64,    // interaction constant, default 1.0
     pub h: f64,    // external uniform magnetic field, default 0.0
     pub beta: f64, // beta = 1/(k_b * T), defaults to 0.43
+    totals: Option<SpinTotals>, // running totals, if tracking is enabled
+    acceptance: AcceptanceTable, // cached Metropolis acceptance probabilities
+    rng: Pcg64,                  // source of all randomness in the lattice, see `seed`
 }
 
-impl Clone for Lattice2d {
-    fn clone(&self) -> Self {
-        Self::new(
+/// Sites handled together by `Lattice2d::sweep_checkerboard`
+const LANES: usize = 8;
+
+/// Metropolis acceptance probabilities for every (neighbour spin sum,
+/// spin) pair, which are the only things dE depends on. Cached in the
+/// lattice and recomputed when j, h or beta change.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug)]
+struct AcceptanceTable {
+    j: f64,
+    h: f64,
+    beta: f64,
+    prob: [f64; 10], // indexed by `AcceptanceTable::index`
+}
+
+impl AcceptanceTable {
+    fn new(j: f64, h: f64, beta: f64) -> Self {
+        let mut prob = [0.0; 10];
+        for neighbour_spin_sum in [-4, -2, 0, 2, 4] {
+            for spin in [-1, 1] {
+                #[allow(non_snake_case)]
+                let dE = 2.0 * j * (neighbour_spin_sum * spin) as f64 + h * spin as f64;
+                prob[Self::index(neighbour_spin_sum, spin)] = (-beta * dE).exp();
+            }
+        }
+        AcceptanceTable { j, h, beta, prob }
+    }
+
+    fn index(neighbour_spin_sum: i32, spin: i32) -> usize {
+        (neighbour_spin_sum + 4) as usize + ((spin + 1) / 2) as usize
+    }
+
+    /// Decides with the Metropolis rule whether to flip a spin with the
+    /// given neighbour spin sum
+    fn accept<R: Rng>(&self, neighbour_spin_sum: i32, spin: i32, rng: &mut R) -> bool {
+        let p = self.prob[Self::index(neighbour_spin_sum, spin)];
+        // flips that lower the energy are always accepted
+        // (something more complicated for spin 3/2)
+        p >= 1.0 || rng.gen::<f64>() < p
+    }
+
+    fn is_for(&self, j: f64, h: f64, beta: f64) -> bool {
+        self.j == j && self.h == h && self.beta == beta
+    }
+
+    /// Probabilities as thresholds for uniform u32s, a flip being
+    /// accepted if r < threshold
+    fn thresholds(&self) -> [u64; 10] {
+        self.prob.map(|p| (p.min(1.0) * 2f64.powi(32)) as u64)
+    }
+}
+
+/// Running totals kept up to date on every accepted flip,
+/// see `Lattice2d::set_tracking`
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SpinTotals {
+    spin_sum: i32, // ∑ s_i
+    bond_sum: i32, // ∑ s_i * s_j over neighbouring pairs, each pair counted once
+}
+
+/// Energy and magnetization recorded after every sweep,
+/// see `Lattice2d::record_time_series`
+pub struct TimeSeries {
+    pub energy: Vec<f64>,        // total energy E
+    pub magnetization: Vec<f64>, // mean spin m
+}
+
+/// Builds a `Lattice2d` from named parameters, checking them, see
+/// `Lattice2d::builder`
+///
+/// ```
+/// use ising_lib::lattice2d::{Lattice2d, InitType};
+///
+/// let lattice = Lattice2d::builder([16, 16])
+///     .init_type(InitType::AllUp)
+///     .temperature(2.0)
+///     .h(0.1)
+///     .seed(7)
+///     .build()
+///     .unwrap();
+/// assert_eq!(lattice.beta, 0.5);
+/// assert!(Lattice2d::builder([0, 16]).build().is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct Lattice2dBuilder {
+    dims: [usize; 2],
+    update_rule: UpdateRule,
+    spin_type: SpinType,
+    init_type: InitType,
+    site_order: SiteOrder,
+    j: f64,
+    h: f64,
+    beta: Beta,
+    seed: Option<u64>,
+    tracking: bool,
+}
+
+/// The inverse temperature as it was given to the builder
+#[derive(Clone, Copy, Debug)]
+enum Beta {
+    Beta(f64),
+    Temperature(f64),
+}
+
+impl Lattice2dBuilder {
+    /// Starts from the parameters of `Lattice2d::new_basic`
+    pub fn new(dims: [usize; 2]) -> Self {
+        Lattice2dBuilder {
+            dims,
+            update_rule: UpdateRule::Metropolis,
+            spin_type: SpinType::SpinHalf,
+            init_type: InitType::Random,
+            site_order: SiteOrder::Random,
+            j: 1.0,
+            h: 0.0,
+            beta: Beta::Beta(0.43),
+            seed: None,
+            tracking: false,
+        }
+    }
+
+    pub fn update_rule(mut self, update_rule: UpdateRule) -> Self {
+        self.update_rule = update_rule;
+        self
+    }
+
+    pub fn spin_type(mut self, spin_type: SpinType) -> Self {
+        self.spin_type = spin_type;
+        self
+    }
+
+    pub fn init_type(mut self, init_type: InitType) -> Self {
+        self.init_type = init_type;
+        self
+    }
+
+    pub fn site_order(mut self, site_order: SiteOrder) -> Self {
+        self.site_order = site_order;
+        self
+    }
+
+    /// Interaction constant, default 1.0
+    pub fn j(mut self, j: f64) -> Self {
+        self.j = j;
+        self
+    }
+
+    /// External field, default 0.0
+    pub fn h(mut self, h: f64) -> Self {
+        self.h = h;
+        self
+    }
+
+    /// Inverse temperature, default 0.43; replaces any temperature set before
+    pub fn beta(mut self, beta: f64) -> Self {
+        self.beta = Beta::Beta(beta);
+        self
+    }
+
+    /// Temperature (in units where k_b = 1), i.e. beta = 1 / temperature;
+    /// replaces any beta set before
+    pub fn temperature(mut self, temperature: f64) -> Self {
+        self.beta = Beta::Temperature(temperature);
+        self
+    }
+
+    /// Seeds the random number generator (and so the initial spins),
+    /// see `Lattice2d::seed`
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Turns on tracking of the running totals, see `Lattice2d::set_tracking`
+    pub fn tracking(mut self, tracking: bool) -> Self {
+        self.tracking = tracking;
+        self
+    }
+
+    /// Checks the parameters and builds the lattice
+    pub fn build(&self) -> Result<Lattice2d, IsingError> {
+        if let UpdateRule::Glauber = self.update_rule {
+            return Err(IsingError::Unimplemented("Glauber updates"));
+        }
+        check_dims(self.dims)?;
+        if self.site_order == SiteOrder::Checkerboard && self.dims.iter().any(|&d| d % 2 != 0) {
+            return Err(IsingError::InvalidDims(self.dims));
+        }
+        for (name, value) in [("j", self.j), ("h", self.h)] {
+            if !value.is_finite() {
+                return Err(IsingError::NonFinite(name, value));
+            }
+        }
+        let beta = match self.beta {
+            Beta::Beta(beta) if !beta.is_finite() => return Err(IsingError::NonFinite("beta", beta)),
+            Beta::Beta(beta) if beta < 0.0 => return Err(IsingError::InvalidTemperature(1.0 / beta)),
+            Beta::Beta(beta) => beta,
+            Beta::Temperature(t) if !t.is_finite() => return Err(IsingError::NonFinite("temperature", t)),
+            Beta::Temperature(t) if t <= 0.0 => return Err(IsingError::InvalidTemperature(t)),
+            Beta::Temperature(t) => 1.0 / t,
+        };
+        let mut lattice = Lattice2d::new(
             self.dims,
             self.update_rule,
             self.spin_type,
             self.init_type,
             self.j,
             self.h,
-            self.beta,
-        )
+            beta,
+        );
+        lattice.site_order = self.site_order;
+        if let Some(seed) = self.seed {
+            lattice.seed(seed);
+            lattice.reset_spins();
+        }
+        lattice.set_tracking(self.tracking);
+        Ok(lattice)
     }
 }
 
 /// Implement basic methods for the 2d lattice type
 impl Lattice2d {
     /// Create a new lattice of given dims with specific implementation details
+    ///
+    /// Panics if a side is 0, `Lattice2d::builder` returns an error instead.
     pub fn new(
         dims: [usize; 2],
         update_rule: UpdateRule,
@@ -78,20 +318,28 @@ impl Lattice2d {
         h: f64,
         beta: f64,
     ) -> Self {
-        let nodes: Array2<i32> = Lattice2d::init_spins(&init_type, &dims);
+        if let Err(e) = check_dims(dims) {
+            panic!("{}", e);
+        }
+        let mut rng = Pcg64::new(rand::thread_rng().gen(), 0);
+        let nodes: Array2<i32> = Lattice2d::init_spins(&init_type, &dims, &mut rng);
 
         let (width, height) = nodes.dim();
 
         Lattice2d {
             dims: [width, height],
             n_sites: width as i32 * height as i32,
-            nodes: nodes, // should it be called notes or sites?
-            update_rule: update_rule,
-            spin_type: spin_type,
-            init_type: init_type,
-            j: j,
-            h: h,
-            beta: beta,
+            nodes, // should it be called notes or sites?
+            update_rule,
+            spin_type,
+            init_type,
+            site_order: SiteOrder::Random,
+            j,
+            h,
+            beta,
+            totals: None,
+            acceptance: AcceptanceTable::new(j, h, beta),
+            rng,
         }
     }
 
@@ -108,30 +356,135 @@ impl Lattice2d {
         )
     }
 
+    /// A new lattice with the same parameters, site order and tracking
+    /// as this one, but with spins initialised afresh as given by
+    /// init_type and a random number generator of its own
+    pub fn fresh_like(&self) -> Self {
+        let mut lattice = Self::new(
+            self.dims,
+            self.update_rule,
+            self.spin_type,
+            self.init_type,
+            self.j,
+            self.h,
+            self.beta,
+        );
+        lattice.site_order = self.site_order;
+        lattice.set_tracking(self.is_tracking());
+        lattice
+    }
+
+    /// Like `fresh_like`, but with the random number generator seeded,
+    /// so the new spins and everything after them are reproducible
+    pub fn fresh_like_seeded(&self, seed: u64) -> Self {
+        let mut lattice = self.fresh_like();
+        lattice.seed(seed);
+        lattice.reset_spins();
+        lattice
+    }
+
+    /// Starts building a lattice of given dims from named parameters,
+    /// which are checked by `Lattice2dBuilder::build`
+    pub fn builder(dims: [usize; 2]) -> Lattice2dBuilder {
+        Lattice2dBuilder::new(dims)
+    }
+
     /// initiates the sites to some config (often random) as specified by init_type
-    fn init_spins(init_type: &InitType, dims: &[usize; 2]) -> Array2<i32> {
-        let nodes: Array2<i32>;
+    fn init_spins<R: Rng>(init_type: &InitType, dims: &[usize; 2], rng: &mut R) -> Array2<i32> {
         match init_type {
-            InitType::Random => {
-                let mut rng = rand::thread_rng();
-                nodes = Array2::from_shape_fn(*dims, |_| *[-1, 1].choose(&mut rng).unwrap());
-            }
-            InitType::AllUp => {
-                nodes = Array2::<i32>::ones(*dims);
-            }
+            InitType::Random => Array2::from_shape_fn(*dims, |_| *[-1, 1].choose(rng).unwrap()),
+            InitType::AllUp => Array2::<i32>::ones(*dims),
         }
-        return nodes;
     }
 
     /// resets the sites to some config (often random) as specified by init_type
     pub fn reset_spins(&mut self) {
-        self.nodes = Lattice2d::init_spins(&self.init_type, &self.dims);
+        self.nodes = Lattice2d::init_spins(&self.init_type, &self.dims, &mut self.rng);
+        if self.is_tracking() {
+            self.set_tracking(true);
+        }
     }
 
-    /// Gets the difference in energy from flipping the spin at [idx0,idx1]
-    #[allow(non_snake_case)] // just for this function
-    fn get_dE(&self, idx0: usize, idx1: usize) -> f64 {
-        let neighbour_spin_sum: i32 = self.nodes[[idx0, (idx1 + 1) % self.dims[1]]]
+    /// Replaces the spins with a prepared configuration (e.g. loaded
+    /// with `config_io`), which may change the dims of the lattice
+    pub fn set_nodes(&mut self, nodes: Array2<i32>) {
+        if let Err(e) = self.try_set_nodes(nodes) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Like `set_nodes`, but returns an error if a value isn't a spin or
+    /// the array is empty, leaving the lattice unchanged
+    pub fn try_set_nodes(&mut self, nodes: Array2<i32>) -> Result<(), IsingError> {
+        let (height, width) = nodes.dim();
+        check_dims([height, width])?;
+        check_spins(&nodes)?;
+        self.dims = [height, width];
+        self.n_sites = height as i32 * width as i32;
+        self.nodes = nodes;
+        if self.is_tracking() {
+            self.set_tracking(true);
+        }
+        Ok(())
+    }
+
+    /// Reseeds the random number generator of the lattice
+    ///
+    /// Every random choice the lattice makes (initial spins, sites and
+    /// flips, the streams of the checkerboard sweeps) is drawn from this
+    /// generator, so two lattices with the same seed, parameters and
+    /// spins evolve identically.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Pcg64::new(seed as u128, 0);
+    }
+
+    /// A generator of its own for a sampler driving the lattice, seeded
+    /// from the lattice's stream so seeded lattices stay reproducible
+    pub(crate) fn fork_rng(&mut self) -> Pcg64 {
+        Pcg64::new(self.rng.gen(), 0)
+    }
+
+    /// Turns tracking of the running spin sum and neighbour dot
+    /// product on or off
+    ///
+    /// While tracking, every accepted flip updates the totals in O(1), so
+    /// `Measurement::measure_energy` and `Measurement::get_spin_sum` no
+    /// longer need a pass over the lattice. Since `j` and `h` only enter
+    /// when the energy is measured, they may be changed freely. If you
+    /// write to `nodes` directly, call `set_tracking(true)` again to
+    /// recompute the totals.
+    pub fn set_tracking(&mut self, enabled: bool) {
+        self.totals = None;
+        if enabled {
+            let spin_sum = self.nodes.sum();
+            let bond_sum = (0..self.dims[0])
+                .flat_map(|idx0| (0..self.dims[1]).map(move |idx1| (idx0, idx1)))
+                .map(|(idx0, idx1)| self.nodes[[idx0, idx1]] * self.neighbour_spin_sum(idx0, idx1))
+                .sum::<i32>()
+                / 2;
+            self.totals = Some(SpinTotals { spin_sum, bond_sum });
+        }
+    }
+
+    /// Returns true if the running totals are being tracked
+    pub fn is_tracking(&self) -> bool {
+        self.totals.is_some()
+    }
+
+    /// Returns the tracked ∑ s_i, if tracking is enabled
+    pub fn tracked_spin_sum(&self) -> Option<i32> {
+        self.totals.map(|t| t.spin_sum)
+    }
+
+    /// Returns the tracked ∑ s_i * s_j over neighbouring pairs (each
+    /// pair counted once), if tracking is enabled
+    pub fn tracked_bond_sum(&self) -> Option<i32> {
+        self.totals.map(|t| t.bond_sum)
+    }
+
+    /// Gets the sum of the four neighbours of the spin at [idx0,idx1]
+    pub(crate) fn neighbour_spin_sum(&self, idx0: usize, idx1: usize) -> i32 {
+        self.nodes[[idx0, (idx1 + 1) % self.dims[1]]]
             + self.nodes[[
                 idx0,
                 match idx1 {
@@ -146,70 +499,513 @@ impl Lattice2d {
                     _ => idx0 - 1,
                 },
                 idx1,
-            ]];
+            ]]
+    }
 
+    /// Gets the difference in energy from flipping the spin at [idx0,idx1]
+    #[allow(non_snake_case)] // just for this function
+    pub fn get_dE(&self, idx0: usize, idx1: usize) -> f64 {
+        let neighbour_spin_sum = self.neighbour_spin_sum(idx0, idx1);
         // two times dot prod of spin w/ it's neighbours
         // this is the energy required to flip
         2.0 * self.j * ((neighbour_spin_sum * self.nodes[[idx0, idx1]]) as f64)
             + self.h * (self.nodes[[idx0, idx1]] as f64)
     }
 
+    /// Mean Metropolis acceptance probability of a flip of a random site
+    /// in the current configuration, i.e. the expected fraction of
+    /// flips accepted by the next few updates
+    pub fn acceptance_rate(&self) -> f64 {
+        // the cached table, unless j, h or beta changed since it was made
+        let table = if self.acceptance.is_for(self.j, self.h, self.beta) {
+            self.acceptance
+        } else {
+            AcceptanceTable::new(self.j, self.h, self.beta)
+        };
+        let total: f64 = self.nodes.indexed_iter()
+            .map(|((idx0, idx1), &spin)| {
+                table.prob[AcceptanceTable::index(self.neighbour_spin_sum(idx0, idx1), spin)].min(1.0)
+            })
+            .sum();
+        total / self.n_sites as f64
+    }
+
+    /// Flips the spin at [idx0,idx1], keeping the running totals up to date
+    pub(crate) fn flip(&mut self, idx0: usize, idx1: usize) {
+        let spin = self.nodes[[idx0, idx1]];
+        if let Some(totals) = self.totals {
+            let neighbour_spin_sum = self.neighbour_spin_sum(idx0, idx1);
+            self.totals = Some(SpinTotals {
+                spin_sum: totals.spin_sum - 2 * spin,
+                bond_sum: totals.bond_sum - 2 * spin * neighbour_spin_sum,
+            });
+        }
+        self.nodes[[idx0, idx1]] = -spin;
+    }
+
+    /// Returns the acceptance table for the current j, h and beta,
+    /// recomputing it if any of them changed
+    fn acceptance_table(&mut self) -> AcceptanceTable {
+        if !self.acceptance.is_for(self.j, self.h, self.beta) {
+            self.acceptance = AcceptanceTable::new(self.j, self.h, self.beta);
+        }
+        self.acceptance
+    }
+
+    /// Decides with the Metropolis rule whether to flip the spin at [idx0,idx1]
+    fn metropolis_accept<R: Rng>(&self, table: &AcceptanceTable, idx0: usize, idx1: usize, rng: &mut R) -> bool {
+        table.accept(self.neighbour_spin_sum(idx0, idx1), self.nodes[[idx0, idx1]], rng)
+    }
+
+    /// Returns an error if the update rule is not implemented
+    fn check_update_rule(&self) -> Result<(), IsingError> {
+        match self.update_rule {
+            UpdateRule::Metropolis => Ok(()),
+            UpdateRule::Glauber => Err(IsingError::Unimplemented("Glauber updates")),
+        }
+    }
+
+    /// Returns an error if the dims don't allow checkerboard sweeps
+    fn check_checkerboard(&self) -> Result<(), IsingError> {
+        match self.dims.iter().all(|d| d.is_multiple_of(2)) {
+            true => Ok(()),
+            false => Err(IsingError::InvalidDims(self.dims)),
+        }
+    }
+
     /// Update the lattice by one timestep, (=one potential flip)
+    ///
+    /// Panics if the update rule is not implemented, see `try_update`.
     pub fn update(&mut self) {
-        match self.update_rule {
-            UpdateRule::Metropolis => {
-                // pick a random index
-                let mut rng = rand::thread_rng();
-                let idx0: usize = rng.gen::<usize>() % self.dims[0];
-                let idx1: usize = rng.gen::<usize>() % self.dims[1];
-                // determine weather to flip or not to flip
-                #[allow(non_snake_case)]
-                let dE: f64 = self.get_dE(idx0, idx1);
-                if dE > 0.0 {
-                    let p: f64 = rng.gen::<f64>(); // random f64 between 0 and 1
-                    if p < (-self.beta * dE).exp() {
-                        self.nodes[[idx0, idx1]] *= -1;
-                    }
-                } else {
-                    self.nodes[[idx0, idx1]] *= -1; // something more complicated for spin 3/2
-                }
-            }
-            UpdateRule::Glauber => {
-                // not yet implemented
-                panic!("Warning Glauber Rule not yet implemented");
-            }
+        if let Err(e) = self.try_update() {
+            panic!("{}", e);
         }
     }
 
+    /// Like `update`, but returns an error if the update rule is not
+    /// implemented
+    pub fn try_update(&mut self) -> Result<(), IsingError> {
+        self.try_update_n(1)
+    }
+
     /// Update the lattice by n timesteps
     pub fn update_n(&mut self, n: usize) {
+        if let Err(e) = self.try_update_n(n) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Like `update_n`, see `try_update`
+    pub fn try_update_n(&mut self, n: usize) -> Result<(), IsingError> {
+        self.check_update_rule()?;
+        self.update_random_sites(n);
+        Ok(())
+    }
+
+    /// n Metropolis updates of randomly picked sites, the update rule
+    /// being checked by the caller
+    fn update_random_sites(&mut self, n: usize) {
+        let table = self.acceptance_table();
+        let mut rng = self.rng.clone();
         for _ in 0..n {
-            self.update();
+            // pick a random index
+            let idx0: usize = rng.gen::<usize>() % self.dims[0];
+            let idx1: usize = rng.gen::<usize>() % self.dims[1];
+            // determine weather to flip or not to flip
+            if self.metropolis_accept(&table, idx0, idx1, &mut rng) {
+                self.flip(idx0, idx1);
+            }
         }
+        self.rng = rng;
     }
 
-    /// Display lattice in terminal
-    pub fn disp_terminal(&self) {
-        let mut string = "----------------\n".to_owned();
+    /// Sweep the lattice once: n_sites attempted flips, visiting the
+    /// sites in the order given by `self.site_order`
+    ///
+    /// One sweep is the natural unit of Monte Carlo time (one Monte Carlo
+    /// step per site), and it doesn't depend on the size of the lattice.
+    /// Panics if the update rule is not implemented, or if the dims are
+    /// odd with checkerboard order, see `try_sweep`.
+    pub fn sweep(&mut self) {
+        if let Err(e) = self.try_sweep() {
+            panic!("{}", e);
+        }
+    }
+
+    /// Like `sweep`, but returns an error instead of panicking
+    pub fn try_sweep(&mut self) -> Result<(), IsingError> {
+        self.check_update_rule()?;
+        match self.site_order {
+            SiteOrder::Random => self.update_random_sites(self.n_sites as usize),
+            SiteOrder::Typewriter => self.sweep_typewriter(),
+            SiteOrder::Checkerboard => {
+                self.check_checkerboard()?;
+                self.sweep_checkerboard();
+            }
+        }
+        Ok(())
+    }
+
+    /// Sweep the lattice n times
+    pub fn sweep_n(&mut self, n: usize) {
+        for _ in 0..n {
+            self.sweep();
+        }
+    }
+
+    /// Like `sweep_n`, see `try_sweep`
+    pub fn try_sweep_n(&mut self, n: usize) -> Result<(), IsingError> {
+        for _ in 0..n {
+            self.try_sweep()?;
+        }
+        Ok(())
+    }
+
+    /// Sweep the lattice once, visiting every site in turn row by row,
+    /// the update rule being checked by the caller
+    fn sweep_typewriter(&mut self) {
+        let table = self.acceptance_table();
+        let mut rng = self.rng.clone();
         for idx0 in 0..self.dims[0] {
-            string = string + "|";
             for idx1 in 0..self.dims[1] {
-                match self.nodes[[idx0, idx1]] {
-                    -1 => {
-                        string = string + " ";
+                if self.metropolis_accept(&table, idx0, idx1, &mut rng) {
+                    self.flip(idx0, idx1);
+                }
+            }
+        }
+        self.rng = rng;
+    }
+
+    /// Sweep the lattice once (n_sites attempted flips) in checkerboard
+    /// order, using all the threads of the rayon thread pool
+    ///
+    /// The sites are split into two sublattices like the squares of a
+    /// checkerboard. Neighbours of a site are all on the other
+    /// sublattice, so each sublattice can be updated in parallel
+    /// while the other one stays fixed, and every single-site update
+    /// still satisfies detailed balance. Each row is updated with its
+    /// own PCG random number stream, in place, one block of rows per
+    /// thread. Both dims must be even so that the checkerboard wraps
+    /// around the torus.
+    pub fn sweep_checkerboard_parallel(&mut self) {
+        if let Err(e) = self.check_checkerboard().and(self.check_update_rule()) {
+            panic!("{}", e);
+        }
+        let table = self.acceptance_table();
+        let seed: u128 = self.rng.gen();
+        if !self.nodes.is_standard_layout() {
+            self.nodes = self.nodes.as_standard_layout().to_owned();
+        }
+        let [height, width] = self.dims;
+        let rows_per_block = height.div_ceil(rayon::current_num_threads().min(height));
+        let nodes = self.nodes.as_slice_mut().unwrap();
+        let (mut spin_delta, mut bond_delta) = (0, 0);
+        for colour in 0..2 {
+            // the rows just above and below each block; only their sites
+            // of the other colour are read, which this half sweep leaves alone
+            let row = |idx0: usize| nodes[idx0 * width..][..width].to_vec();
+            let halos: Vec<(Vec<i32>, Vec<i32>)> = (0..height)
+                .step_by(rows_per_block)
+                .map(|first| {
+                    let last = (first + rows_per_block).min(height) - 1;
+                    (row((first + height - 1) % height), row((last + 1) % height))
+                })
+                .collect();
+            // changes to the running totals from each block of rows
+            let deltas = nodes
+                .par_chunks_mut(rows_per_block * width)
+                .zip(halos.par_iter())
+                .enumerate()
+                .map(|(block, (block_nodes, (above, below)))| {
+                    let n_block_rows = block_nodes.len() / width;
+                    let mut deltas = (0, 0);
+                    for r in 0..n_block_rows {
+                        let idx0 = block * rows_per_block + r;
+                        let (before, rest) = block_nodes.split_at_mut(r * width);
+                        let (this_row, after) = rest.split_at_mut(width);
+                        let up_row = if r == 0 { &above[..] } else { &before[(r - 1) * width..] };
+                        let down_row = if r + 1 == n_block_rows { &below[..] } else { &after[..width] };
+                        let mut rng = Pcg64::new(seed, (colour * height + idx0) as u128);
+                        for idx1 in ((idx0 + colour) % 2..width).step_by(2) {
+                            let spin = this_row[idx1];
+                            let neighbour_spin_sum = up_row[idx1]
+                                + down_row[idx1]
+                                + this_row[(idx1 + width - 1) % width]
+                                + this_row[(idx1 + 1) % width];
+                            if table.accept(neighbour_spin_sum, spin, &mut rng) {
+                                deltas.0 -= 2 * spin;
+                                deltas.1 -= 2 * spin * neighbour_spin_sum;
+                                this_row[idx1] = -spin;
+                            }
+                        }
                     }
-                    1 => {
-                        string = string + "#";
+                    deltas
+                })
+                .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+            spin_delta += deltas.0;
+            bond_delta += deltas.1;
+        }
+        if let Some(totals) = self.totals {
+            self.totals = Some(SpinTotals {
+                spin_sum: totals.spin_sum + spin_delta,
+                bond_sum: totals.bond_sum + bond_delta,
+            });
+        }
+    }
+
+    /// Sweep the lattice once (n_sites attempted flips) in checkerboard
+    /// order on the current thread
+    ///
+    /// Like `sweep_checkerboard_parallel`, but the sites of a row are
+    /// handled LANES at a time in fixed-size arrays: first the neighbour
+    /// sums, then a lookup in the cached acceptance table and a
+    /// comparison with random u32s, then the flips. The neighbours are
+    /// read from shifted copies of the row, so the loops over lanes have
+    /// no wrap-around indexing. Both dims must be even.
+    pub fn sweep_checkerboard(&mut self) {
+        if let Err(e) = self.check_checkerboard().and(self.check_update_rule()) {
+            panic!("{}", e);
+        }
+        let thresholds = self.acceptance_table().thresholds();
+        let mut rng = Pcg64::new(self.rng.gen(), 0);
+        if !self.nodes.is_standard_layout() {
+            self.nodes = self.nodes.as_standard_layout().to_owned();
+        }
+        let [height, width] = self.dims;
+        let nodes = self.nodes.as_slice_mut().unwrap();
+        let (mut spin_delta, mut bond_delta) = (0, 0);
+        // scratch space, reused for every row
+        let mut left = vec![0; width];
+        let mut right = vec![0; width];
+        for colour in 0..2 {
+            for idx0 in 0..height {
+                let up = ((idx0 + height - 1) % height) * width;
+                let down = ((idx0 + 1) % height) * width;
+                // the row shifted by one site either way, wrapping around
+                let row = &nodes[idx0 * width..][..width];
+                left[1..].copy_from_slice(&row[..width - 1]);
+                left[0] = row[width - 1];
+                right[..width - 1].copy_from_slice(&row[1..]);
+                right[width - 1] = row[0];
+
+                // sites of this colour sit on every other column
+                let first = (idx0 + colour) % 2;
+                for base in (0..width / 2).step_by(LANES) {
+                    let n_lanes = LANES.min(width / 2 - base);
+                    let column = |l: usize| first + 2 * (base + l);
+                    // lanes past the end of the row keep a valid (spin, sum)
+                    // pair for the table lookup, and are never flipped
+                    let mut spin = [1i32; LANES];
+                    let mut neighbour_spin_sum = [0i32; LANES];
+                    let mut random = [0u32; LANES];
+                    for l in 0..n_lanes {
+                        let idx1 = column(l);
+                        spin[l] = nodes[idx0 * width + idx1];
+                        neighbour_spin_sum[l] = nodes[up + idx1] + nodes[down + idx1] + left[idx1] + right[idx1];
                     }
-                    _ => {
-                        panic!("Ising lattice is an array of -1s and 1s");
+                    random.iter_mut().for_each(|r| *r = rng.gen());
+                    let mut flip = [false; LANES];
+                    for l in 0..LANES {
+                        let threshold = thresholds[AcceptanceTable::index(neighbour_spin_sum[l], spin[l])];
+                        flip[l] = (random[l] as u64) < threshold;
+                    }
+                    for l in 0..n_lanes {
+                        if flip[l] {
+                            nodes[idx0 * width + column(l)] = -spin[l];
+                            spin_delta -= 2 * spin[l];
+                            bond_delta -= 2 * spin[l] * neighbour_spin_sum[l];
+                        }
                     }
                 }
             }
-            string = string + "|\n";
         }
-        string = string + "---------------------";
-        println!("{}", string);
+        if let Some(totals) = self.totals {
+            self.totals = Some(SpinTotals {
+                spin_sum: totals.spin_sum + spin_delta,
+                bond_sum: totals.bond_sum + bond_delta,
+            });
+        }
+    }
+
+    /// Runs the lattice for n_sweeps sweeps (see `sweep`), recording the
+    /// energy and magnetization after every sweep
+    ///
+    /// Turns on tracking of the running totals, so recording costs O(1)
+    /// per sweep.
+    pub fn record_time_series(&mut self, n_sweeps: usize) -> TimeSeries {
+        if !self.is_tracking() {
+            self.set_tracking(true);
+        }
+        let mut series = TimeSeries {
+            energy: Vec::with_capacity(n_sweeps),
+            magnetization: Vec::with_capacity(n_sweeps),
+        };
+        for _ in 0..n_sweeps {
+            self.sweep();
+            series.energy.push(self.measure_energy());
+            series.magnetization.push(self.get_spin_mean());
+        }
+        series
+    }
+
+    /// Display lattice in terminal, see the `Display` impl
+    pub fn disp_terminal(&self) {
+        println!("{:#}", self);
+    }
+
+    /// Like `disp_terminal`, but returns an error instead of drawing
+    /// values that are not spins
+    pub fn try_disp_terminal(&self) -> Result<(), IsingError> {
+        check_spins(&self.nodes)?;
+        self.disp_terminal();
+        Ok(())
+    }
+}
+
+/// Returns an error if a side of the lattice is 0
+fn check_dims(dims: [usize; 2]) -> Result<(), IsingError> {
+    if dims.contains(&0) {
+        Err(IsingError::InvalidDims(dims))
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns an error if a value isn't a spin ±1
+fn check_spins(nodes: &Array2<i32>) -> Result<(), IsingError> {
+    match nodes.iter().find(|&&s| s != -1 && s != 1) {
+        Some(&s) => Err(IsingError::InvalidSpin(s)),
+        None => Ok(()),
+    }
+}
+
+/// Draws the lattice one row per line, up spins as `#` and down spins as
+/// `.` (anything else as `?`), without a trailing newline. The alternate
+/// form `{:#}` draws a border around it. Either form is read back by
+/// `str::parse`.
+impl fmt::Display for Lattice2d {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let border = format!("+{}+", "-".repeat(self.dims[1]));
+        if f.alternate() {
+            writeln!(f, "{}", border)?;
+        }
+        for (idx0, row) in self.nodes.rows().into_iter().enumerate() {
+            if idx0 > 0 {
+                writeln!(f)?;
+            }
+            let line: String = row.iter()
+                .map(|&s| match s {
+                    1 => '#',
+                    -1 => '.',
+                    _ => '?',
+                })
+                .collect();
+            if f.alternate() {
+                write!(f, "|{}|", line)?;
+            } else {
+                write!(f, "{}", line)?;
+            }
+        }
+        if f.alternate() {
+            write!(f, "\n{}", border)?;
+        }
+        Ok(())
+    }
+}
+
+/// Summarises the parameters of the lattice, not the spins (use
+/// `Display` for those)
+impl fmt::Debug for Lattice2d {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Lattice2d")
+            .field("dims", &self.dims)
+            .field("update_rule", &self.update_rule)
+            .field("spin_type", &self.spin_type)
+            .field("init_type", &self.init_type)
+            .field("site_order", &self.site_order)
+            .field("j", &self.j)
+            .field("h", &self.h)
+            .field("beta", &self.beta)
+            .field("tracking", &self.is_tracking())
+            .finish()
+    }
+}
+
+/// Error returned when parsing a lattice from text fails
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseLatticeError {
+    pub line: usize, // line of the text (from 1) where parsing failed, 0 if it's about the whole text
+    pub msg: String,
+}
+
+impl fmt::Display for ParseLatticeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            0 => write!(f, "invalid lattice: {}", self.msg),
+            line => write!(f, "invalid lattice at line {}: {}", line, self.msg),
+        }
+    }
+}
+
+impl Error for ParseLatticeError {}
+
+/// Parses a configuration drawn with `#` for up spins and `.` for down
+/// spins, one row per line, into a lattice with the parameters of
+/// `Lattice2d::new_basic`
+///
+/// Blank lines and whitespace around the rows are ignored, and so is a
+/// border like the one drawn by `{:#}`, so configurations can be
+/// written inline in tests:
+///
+/// ```
+/// use ising_lib::lattice2d::Lattice2d;
+/// use ising_lib::measurement::Measurement;
+///
+/// // two stripes: the 16 vertical bonds are aligned (and counted from
+/// // both ends), the horizontal ones cancel out
+/// let lattice: Lattice2d = "
+///     ..##
+///     ..##
+///     ..##
+///     ..##
+/// ".parse().unwrap();
+/// assert_eq!(lattice.dims, [4, 4]);
+/// assert_eq!(lattice.measure_energy(), -32.0);
+/// ```
+impl FromStr for Lattice2d {
+    type Err = ParseLatticeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rows: Vec<Vec<i32>> = vec![];
+        for (idx, line) in s.lines().enumerate() {
+            let error = |msg: String| ParseLatticeError { line: idx + 1, msg };
+            let line = line.trim();
+            if line.is_empty() || (line.starts_with('+') && line.chars().all(|c| c == '+' || c == '-')) {
+                continue;
+            }
+            let line = line.strip_prefix('|').and_then(|l| l.strip_suffix('|')).unwrap_or(line);
+            let row = line.chars()
+                .map(|c| match c {
+                    '#' => Ok(1),
+                    '.' => Ok(-1),
+                    _ => Err(error(format!("expected '#' or '.', found {:?}", c))),
+                })
+                .collect::<Result<Vec<i32>, _>>()?;
+            if let Some(first) = rows.first() {
+                if row.len() != first.len() {
+                    return Err(error(format!("row of {} sites, expected {}", row.len(), first.len())));
+                }
+            }
+            rows.push(row);
+        }
+        if rows.is_empty() || rows[0].is_empty() {
+            return Err(ParseLatticeError { line: 0, msg: "no sites".to_owned() });
+        }
+        let dims = [rows.len(), rows[0].len()];
+        let nodes = Array2::from_shape_vec(dims, rows.concat()).unwrap();
+        let mut lattice = Lattice2d::new_basic(dims);
+        lattice.set_nodes(nodes);
+        Ok(lattice)
     }
 }
 
@@ -248,8 +1044,36 @@ mod tests {
 
     #[test]
     fn test_clone_lattice() {
-        let lattice = Lattice2d::new_basic([3, 3]);
-        let _newlat = lattice.clone();
+        let mut lattice = Lattice2d::new_basic([3, 3]);
+        lattice.sweep();
+        let mut newlat = lattice.clone();
+        assert_eq!(newlat.nodes, lattice.nodes);
+        // the random number generator is copied too
+        lattice.sweep_n(3);
+        newlat.sweep_n(3);
+        assert_eq!(newlat.nodes, lattice.nodes);
+    }
+
+    #[test]
+    fn test_fresh_like() {
+        let mut lattice = Lattice2d::builder([6, 4])
+            .init_type(InitType::AllUp)
+            .site_order(SiteOrder::Typewriter)
+            .h(0.2)
+            .tracking(true)
+            .build()
+            .unwrap();
+        lattice.nodes[[0, 0]] = -1;
+        let fresh = lattice.fresh_like();
+        assert_eq!(fresh.nodes, Array2::<i32>::ones([6, 4]));
+        assert_eq!(fresh.site_order, SiteOrder::Typewriter);
+        assert_eq!(fresh.h, 0.2);
+        assert!(fresh.is_tracking());
+
+        lattice.init_type = InitType::Random;
+        let fresh = lattice.fresh_like_seeded(3);
+        assert_eq!(fresh.nodes, lattice.fresh_like_seeded(3).nodes);
+        assert_eq!(fresh.tracked_spin_sum(), Some(fresh.nodes.sum()));
     }
 
     #[test]
@@ -267,29 +1091,268 @@ mod tests {
         let _dE: f64 = lattice.get_dE(i0, i1);
     }
 
+    #[test]
+    fn test_acceptance_rate() {
+        let mut lattice = Lattice2d::builder([4, 6])
+            .init_type(InitType::AllUp)
+            .beta(0.3)
+            .seed(1)
+            .build()
+            .unwrap();
+        // every flip in the ground state costs dE = 8
+        assert!((lattice.acceptance_rate() - (-8.0 * 0.3f64).exp()).abs() < 1e-12);
+        lattice.beta = 0.0;
+        assert_eq!(lattice.acceptance_rate(), 1.0);
+    }
+
     #[test]
     fn test_init_spins() {
         let nodes: Array2<i32> =
-            Lattice2d::init_spins(&InitType::Random, &[4 as usize, 5 as usize]);
+            Lattice2d::init_spins(&InitType::Random, &[4usize, 5usize], &mut rand::thread_rng());
         let (width, height) = nodes.dim();
         assert_eq!(width, 4usize);
         assert_eq!(height, 5usize);
         assert!(nodes[[3, 4]] == 1 || nodes[[3, 4]] == -1);
         assert!(nodes[[0, 0]] == 1 || nodes[[0, 0]] == -1);
 
-        let nodes: Array2<i32> = Lattice2d::init_spins(&InitType::AllUp, &[2 as usize, 3 as usize]);
+        let nodes: Array2<i32> = Lattice2d::init_spins(&InitType::AllUp, &[2usize, 3usize], &mut rand::thread_rng());
         let (width, height) = nodes.dim();
         assert_eq!(width, 2usize);
         assert_eq!(height, 3usize);
         assert_eq!(nodes[[1, 1]], 1i32);
     }
 
+    #[test]
+    fn test_tracking() {
+        let mut lattice = Lattice2d::builder([6, 7]).h(0.3).beta(0.3).seed(1).build().unwrap();
+        lattice.set_tracking(true);
+        lattice.update_n(5_000);
+        let tracked = lattice.totals;
+        let (energy, spin_sum) = (lattice.measure_energy(), lattice.get_spin_sum());
+        // recompute from scratch
+        lattice.set_tracking(false);
+        assert_eq!(lattice.get_spin_sum(), spin_sum);
+        assert!((lattice.measure_energy() - energy).abs() < 1e-9);
+        lattice.set_tracking(true);
+        assert_eq!(lattice.totals, tracked);
+
+        lattice.reset_spins();
+        let resynced = lattice.totals;
+        lattice.set_tracking(true);
+        assert_eq!(lattice.totals, resynced);
+    }
+
+    #[test]
+    fn test_sweep_checkerboard_parallel() {
+        let mut lattice = Lattice2d::builder([8, 6]).h(0.2).beta(0.4).seed(1).build().unwrap();
+        lattice.set_tracking(true);
+        for _ in 0..20 {
+            lattice.sweep_checkerboard_parallel();
+        }
+        assert!(lattice.nodes.iter().all(|&s| s == 1 || s == -1));
+        let tracked = lattice.totals;
+        lattice.set_tracking(true);
+        assert_eq!(lattice.totals, tracked);
+
+        // at very low temperature the all-up state stays put
+        let mut lattice = Lattice2d::builder([4, 4])
+            .init_type(InitType::AllUp)
+            .beta(100.0)
+            .seed(1)
+            .build()
+            .unwrap();
+        lattice.sweep_checkerboard_parallel();
+        assert!(lattice.nodes.iter().all(|&s| s == 1));
+    }
+
+    #[test]
+    fn test_sweep_checkerboard() {
+        let mut lattice = Lattice2d::builder([6, 10]).h(-0.2).beta(0.4).seed(1).build().unwrap();
+        lattice.set_tracking(true);
+        for _ in 0..20 {
+            lattice.sweep_checkerboard();
+        }
+        let tracked = lattice.totals;
+        lattice.set_tracking(true);
+        assert_eq!(lattice.totals, tracked);
+
+        // at beta = 0 every flip is accepted
+        let mut lattice = Lattice2d::builder([4, 4])
+            .init_type(InitType::AllUp)
+            .beta(0.0)
+            .seed(1)
+            .build()
+            .unwrap();
+        lattice.sweep_checkerboard();
+        assert!(lattice.nodes.iter().all(|&s| s == -1));
+        // the table follows changes to beta
+        lattice.beta = 100.0;
+        lattice.sweep_checkerboard();
+        assert!(lattice.nodes.iter().all(|&s| s == -1));
+    }
+
+    #[test]
+    fn test_sweep_site_orders() {
+        for site_order in [SiteOrder::Random, SiteOrder::Typewriter, SiteOrder::Checkerboard] {
+            let mut lattice = Lattice2d::builder([4, 6])
+                .init_type(InitType::AllUp)
+                .beta(0.0)
+                .seed(1)
+                .build()
+                .unwrap();
+            lattice.site_order = site_order;
+            lattice.set_tracking(true);
+            lattice.sweep_n(3);
+            let tracked = lattice.totals;
+            lattice.set_tracking(true);
+            assert_eq!(lattice.totals, tracked);
+            if site_order != SiteOrder::Random {
+                // at beta = 0 every site is flipped once per sweep
+                assert!(lattice.nodes.iter().all(|&s| s == -1));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_lattice_new_zero_dims() {
+        Lattice2d::new_basic([0, 4]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sweep_checkerboard_parallel_odd_dims() {
+        let mut lattice = Lattice2d::new_basic([5, 6]);
+        lattice.sweep_checkerboard_parallel();
+    }
+
+    #[test]
+    fn test_record_time_series() {
+        let mut lattice = Lattice2d::new_basic([5, 5]);
+        let series = lattice.record_time_series(20);
+        assert_eq!(series.energy.len(), 20);
+        assert_eq!(series.magnetization.len(), 20);
+        assert!(lattice.is_tracking());
+    }
+
     #[test]
     fn test_reset_spins() {
         let mut lattice = Lattice2d::new_basic([5, 10]);
         lattice.reset_spins(); // all we test for here is runtime errors
     }
 
+    #[test]
+    fn test_builder() {
+        let lattice = Lattice2d::builder([4, 6]).build().unwrap();
+        let basic = Lattice2d::new_basic([4, 6]);
+        assert_eq!(format!("{:?}", lattice), format!("{:?}", basic));
+
+        let lattice = Lattice2d::builder([4, 6])
+            .init_type(InitType::AllUp)
+            .site_order(SiteOrder::Checkerboard)
+            .j(-1.0)
+            .h(0.25)
+            .temperature(4.0)
+            .tracking(true)
+            .build()
+            .unwrap();
+        assert_eq!((lattice.j, lattice.h, lattice.beta), (-1.0, 0.25, 0.25));
+        assert_eq!(lattice.site_order, SiteOrder::Checkerboard);
+        assert_eq!(lattice.tracked_spin_sum(), Some(24));
+
+        let a = Lattice2d::builder([5, 5]).seed(3).build().unwrap();
+        let b = Lattice2d::builder([5, 5]).seed(3).build().unwrap();
+        assert_eq!(a.nodes, b.nodes);
+    }
+
+    #[test]
+    fn test_builder_errors() {
+        assert_eq!(Lattice2d::builder([0, 4]).build().unwrap_err(), IsingError::InvalidDims([0, 4]));
+        let odd = Lattice2d::builder([3, 4]).site_order(SiteOrder::Checkerboard).build();
+        assert_eq!(odd.unwrap_err(), IsingError::InvalidDims([3, 4]));
+        assert_eq!(
+            Lattice2d::builder([4, 4]).temperature(-1.0).build().unwrap_err(),
+            IsingError::InvalidTemperature(-1.0)
+        );
+        assert_eq!(
+            Lattice2d::builder([4, 4]).temperature(0.0).build().unwrap_err(),
+            IsingError::InvalidTemperature(0.0)
+        );
+        assert!(matches!(Lattice2d::builder([4, 4]).beta(-0.5).build(), Err(IsingError::InvalidTemperature(_))));
+        assert_eq!(
+            Lattice2d::builder([4, 4]).j(f64::NAN).build().unwrap_err().to_string(),
+            "j must be finite, got NaN"
+        );
+        assert!(matches!(
+            Lattice2d::builder([4, 4]).h(f64::INFINITY).build(),
+            Err(IsingError::NonFinite("h", _))
+        ));
+        // the last of beta and temperature wins
+        assert_eq!(Lattice2d::builder([4, 4]).temperature(-1.0).beta(0.3).build().unwrap().beta, 0.3);
+    }
+
+    #[test]
+    fn test_fallible_updates() {
+        let mut lattice = Lattice2d::new_basic([4, 4]);
+        lattice.update_rule = UpdateRule::Glauber;
+        let err = lattice.try_sweep().unwrap_err();
+        assert_eq!(err, IsingError::Unimplemented("Glauber updates"));
+        assert_eq!(err.to_string(), "Glauber updates not yet implemented");
+        assert!(lattice.try_update_n(3).is_err());
+        assert!(matches!(Lattice2d::builder([4, 4]).update_rule(UpdateRule::Glauber).build(), Err(IsingError::Unimplemented(_))));
+
+        let mut lattice = Lattice2d::new_basic([5, 6]);
+        lattice.site_order = SiteOrder::Checkerboard;
+        assert_eq!(lattice.try_sweep_n(2), Err(IsingError::InvalidDims([5, 6])));
+        lattice.site_order = SiteOrder::Typewriter;
+        assert_eq!(lattice.try_sweep_n(2), Ok(()));
+
+        let before = lattice.nodes.clone();
+        assert_eq!(lattice.try_set_nodes(array![[1, 0], [-1, 1]]), Err(IsingError::InvalidSpin(0)));
+        assert_eq!(lattice.nodes, before);
+        assert_eq!(lattice.try_set_nodes(Array2::zeros([0, 3])), Err(IsingError::InvalidDims([0, 3])));
+        assert_eq!(lattice.dims, [5, 6]);
+        lattice.nodes[[0, 0]] = 2;
+        assert_eq!(lattice.try_disp_terminal(), Err(IsingError::InvalidSpin(2)));
+    }
+
+    #[test]
+    fn test_display_parse() {
+        let lattice: Lattice2d = "
+            #..#
+            ####
+            ..#.
+        ".parse().unwrap();
+        assert_eq!(lattice.dims, [3, 4]);
+        assert_eq!(lattice.n_sites, 12);
+        assert_eq!(lattice.nodes.row(2), array![-1, -1, 1, -1]);
+        assert_eq!(lattice.to_string(), "#..#\n####\n..#.");
+        let boxed = format!("{:#}", lattice);
+        assert_eq!(boxed, "+----+\n|#..#|\n|####|\n|..#.|\n+----+");
+        let parsed: Lattice2d = boxed.parse().unwrap();
+        assert_eq!(parsed.nodes, lattice.nodes);
+
+        // 12 of the 24 bonds are aligned, and 7 spins are up against 5 down
+        let mut lattice = lattice;
+        lattice.h = 0.5;
+        assert_eq!(lattice.get_dot_spin_neighbours(), 0);
+        assert_eq!(lattice.get_spin_sum(), 2);
+        assert_eq!(lattice.measure_energy(), -0.5 * 2.0);
+
+        let error = "##\n#".parse::<Lattice2d>().unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!("#x".parse::<Lattice2d>().is_err());
+        assert_eq!("\n  \n".parse::<Lattice2d>().unwrap_err().line, 0);
+    }
+
+    #[test]
+    fn test_debug() {
+        let lattice = Lattice2d::new_basic([3, 5]);
+        let debug = format!("{:?}", lattice);
+        assert!(debug.starts_with("Lattice2d { dims: [3, 5], update_rule: Metropolis"));
+        assert!(debug.contains("beta: 0.43"));
+    }
+
     #[test]
     fn test_update_disp() {
         let mut lattice = Lattice2d::new_basic([5, 5]);
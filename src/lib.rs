@@ -6,9 +6,24 @@
 //! Refer to the github repository for [examples](https://github.com/micouy/ising_lib). 
 
 
+pub mod analysis;
+pub mod checkpoint;
+pub mod config_io;
+pub mod error;
+pub mod exact;
+pub mod graph;
+pub mod image_export;
 pub mod lattice2d;
 pub mod measurement;
 pub mod monte_carlo_measurement;
+pub mod multicanonical;
+pub mod observable;
+pub mod packed_lattice2d;
+pub mod parallel_tempering;
+pub mod parameter_sweep;
+pub mod results_writer;
+pub mod reweighting;
+pub mod viewer;
+pub mod wang_landau;
 // pub mod prelude; // TODO: do this
-// pub mod graph; // TODO: implement this
 
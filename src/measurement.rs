@@ -4,6 +4,7 @@
 //! multiple samples.
 
 use ndarray::prelude::*;
+use rustfft::{num_complex::Complex, FftDirection, FftPlanner};
 use crate::lattice2d::*;
 
 /// The measurement trait measures quantities across different graphs.
@@ -15,12 +16,92 @@ pub trait Measurement {
     fn get_spin_mean(&self) -> f64;     // get the mean value of spins 
     fn _convolve_2d_circ_neighbours(mat:&Array2<i32>) -> Array2<i32>; // convolves mat with filt with circular boundary conditions
     fn get_dot_spin_neighbours(&self) -> i32;   // get dot-product of each spin with the sum of it's neighbours
+    fn get_neighbour_correlation(&self) -> f64; // get mean of s_i * s_j over neighbouring pairs
     fn measure_energy(&self) -> f64;            // get total energy of system
     fn measure_energy_per_spin(&self) -> f64;   // get energy divided by number of sites
-    // IDEAS
-    // spacial correlation function
-        // correlation with immediate and 2nd degree neighbors... more?
-        // is there a fast way to implement this?
+    fn get_structure_factor(&self) -> Array2<f64>;  // get |FFT(spins)|² / n for every wave vector
+    fn get_spin_correlations(&self) -> Array2<f64>; // get (1/n) ∑_x s_x * s_(x+r) for every displacement r
+}
+
+/// Performs an in-place 2d FFT (rows, then columns) of `data`
+/// The transform is unnormalized, like rustfft's 1d transforms
+fn fft_2d(data: &mut Array2<Complex<f64>>, direction: FftDirection) {
+    let mut planner = FftPlanner::new();
+    for axis in [Axis(1), Axis(0)] {
+        let fft = planner.plan_fft(data.len_of(axis), direction);
+        let mut buffer = vec![Complex::new(0.0, 0.0); data.len_of(axis)];
+        for mut lane in data.lanes_mut(axis) {
+            for (b, x) in buffer.iter_mut().zip(lane.iter()) {
+                *b = *x;
+            }
+            fft.process(&mut buffer);
+            for (x, b) in lane.iter_mut().zip(buffer.iter()) {
+                *x = *b;
+            }
+        }
+    }
+}
+
+/// Structure factor |FFT(spins)|² / n of a spin configuration,
+/// see `Measurement::get_structure_factor`
+pub(crate) fn structure_factor(nodes: &Array2<i32>) -> Array2<f64> {
+    let n_sites = nodes.len() as f64;
+    let mut spins = nodes.mapv(|s| Complex::new(s as f64, 0.0));
+    fft_2d(&mut spins, FftDirection::Forward);
+    spins.mapv(|c| c.norm_sqr() / n_sites)
+}
+
+/// Inverse of the structure factor: since S(k) is the Fourier transform
+/// of the correlations C(r), an inverse FFT recovers C(r). This is linear,
+/// so it also turns an averaged S(k) into averaged correlations.
+pub(crate) fn correlations_from_structure_factor(structure_factor: &Array2<f64>) -> Array2<f64> {
+    let n_sites = structure_factor.len() as f64;
+    let mut s_k = structure_factor.mapv(|s| Complex::new(s, 0.0));
+    fft_2d(&mut s_k, FftDirection::Inverse);
+    s_k.mapv(|c| c.re / n_sites)
+}
+
+/// Radially averages a correlation array indexed by displacement
+/// (dx, dy), as returned by `get_spin_correlations`
+///
+/// Displacements use the minimum image convention, and every distinct
+/// distance r = sqrt(dx² + dy²) gets its own bin. Returns the pairs
+/// (r, G(r)) sorted by r.
+pub fn radial_average(g: &Array2<f64>) -> Vec<(f64, f64)> {
+    let (width, height) = g.dim();
+    // bins indexed by r², which is an integer on the lattice
+    let mut bins = std::collections::BTreeMap::<usize, (f64, usize)>::new();
+    for ((dx, dy), &value) in g.indexed_iter() {
+        let dx = dx.min(width - dx);
+        let dy = dy.min(height - dy);
+        let bin = bins.entry(dx * dx + dy * dy).or_insert((0.0, 0));
+        bin.0 += value;
+        bin.1 += 1;
+    }
+    bins.into_iter()
+        .map(|(r2, (sum, count))| ((r2 as f64).sqrt(), sum / count as f64))
+        .collect()
+}
+
+/// Second moment correlation length from a structure factor S(k)
+///
+/// ```text
+/// ξ = sqrt(S(0) / S(k_min) - 1) / (2 sin(k_min / 2))
+/// ```
+///
+/// where k_min = 2π / L is the smallest nonzero momentum along an axis.
+/// The estimate is made along both axes and averaged. S(0) should
+/// be the full (disconnected) value, i.e. n * <m²>.
+pub fn second_moment_correlation_length(structure_factor: &Array2<f64>) -> f64 {
+    let (width, height) = structure_factor.dim();
+    let s_zero = structure_factor[[0, 0]];
+    let xi = |s_k_min: f64, len: usize| {
+        let k_min = 2.0 * std::f64::consts::PI / len as f64;
+        (s_zero / s_k_min - 1.0).max(0.0).sqrt() / (2.0 * (k_min / 2.0).sin())
+    };
+    let xi_0 = xi(structure_factor[[1 % width, 0]], width);
+    let xi_1 = xi(structure_factor[[0, 1 % height]], height);
+    (xi_0 + xi_1) / 2.0
 }
 
 /// Implement the measurement trait for the Lattice2d type
@@ -28,8 +109,10 @@ impl Measurement for Lattice2d {
     /// method returns sum of spins in lattice
     /// ∑ s_i
     fn get_spin_sum(&self) -> i32 {
-        self.nodes.iter()
-            .fold(0 , |acc, &x| acc + x)
+        match self.tracked_spin_sum() {
+            Some(spin_sum) => spin_sum,
+            None => self.nodes.iter().sum(),
+        }
     }
 
     /// method returns mean spin of lattice
@@ -66,6 +149,9 @@ impl Measurement for Lattice2d {
         // dot product of result with all_sites
         // (There may be room for optimization here... possibly a 2x speed 
         // up... at the expense of readable code?)
+        if let Some(bond_sum) = self.tracked_bond_sum() {
+            return 2 * bond_sum; // each pair is counted from both ends
+        }
         let neighbors = Lattice2d::_convolve_2d_circ_neighbours(&self.nodes);
         assert_eq!(neighbors.shape() , self.nodes.shape());
         let mut dot_spin:i32 = 0;
@@ -74,6 +160,13 @@ impl Measurement for Lattice2d {
         }
         dot_spin 
     }
+
+    /// method returns the nearest neighbour correlation
+    /// ∑ (s_i * s_j) / (4 n), between -1 and +1
+    fn get_neighbour_correlation(&self) -> f64 {
+        // dividing by 4.0 scales it between -1 and +1, since 4 neighbours
+        self.get_dot_spin_neighbours() as f64 / self.n_sites as f64 / 4.0
+    }
     /// Return the energy of the lattice
     ///
     /// ```text
@@ -84,12 +177,33 @@ impl Measurement for Lattice2d {
         let spin_neighbours_dot = self.get_dot_spin_neighbours() as f64; // J term
         // Q: should we take precautions in case of overflow errors here 
         // when converting from i32 to f64 ? 
-        return - self.j * spin_neighbours_dot - self.h * spin_sum 
+        - self.j * spin_neighbours_dot - self.h * spin_sum
     }
     /// Returns the energy per spin
     fn measure_energy_per_spin(&self) -> f64 {
         self.measure_energy() / self.n_sites as f64 
     }
+
+    /// Returns the structure factor of the lattice
+    ///
+    /// ```text
+    /// S(k) = |∑_x s_x exp(-i k.x)|² / n
+    /// ```
+    ///
+    /// indexed by the Fourier mode (k0, k1), where k = 2π (k0/L0, k1/L1)
+    fn get_structure_factor(&self) -> Array2<f64> {
+        structure_factor(&self.nodes)
+    }
+
+    /// Returns the spin-spin correlations for all displacements,
+    /// computed from the structure factor via an inverse FFT
+    ///
+    /// ```text
+    /// C(r) = (1/n) ∑_x s_x * s_(x+r)
+    /// ```
+    fn get_spin_correlations(&self) -> Array2<f64> {
+        correlations_from_structure_factor(&self.get_structure_factor())
+    }
 }
 
 #[cfg(test)]
@@ -121,24 +235,24 @@ mod test {
 
     #[test]
     fn test_convolve_2d_circ_neighbours() {
-        let vec1 = vec![
+        let vec1 = [
             vec![0,0,0],
             vec![0,1,0],
             vec![0,0,0],
         ];
-        let vec1_conv = vec![ // we expect vec1 to convolve into this
+        let vec1_conv = [ // we expect vec1 to convolve into this
             vec![0,1,0],
             vec![1,0,1],
             vec![0,1,0],
         ];
-        let vec2 = vec![
+        let vec2 = [
             vec![0,0,0,1],
             vec![0,0,0,0],
             vec![0,0,0,0],
             vec![0,0,0,0],
         ];
         // we expect the convolution operator to turn vec2 into vec2_conv
-        let vec2_conv = vec![
+        let vec2_conv = [
             vec![1,0,1,0],
             vec![0,0,0,1],
             vec![0,0,0,0],
@@ -184,6 +298,55 @@ mod test {
         assert_eq!(lattice.get_dot_spin_neighbours() , 6 * 4);
     }
 
+    #[test]
+    fn test_measure_energy() {
+        let lattice = Lattice2d::new([4,4],
+            UpdateRule::Metropolis,
+            SpinType::SpinHalf,
+            InitType::AllUp,
+            1.0, // interaction constant, default 1.0
+            0.5, // external uniform magnetic field, default 0.0
+            0.5, // beta = 1/(k_b * T), defaults to 0.43
+        );
+        // 16 spins, all aligned with their 4 neighbours and the field
+        assert_eq!(lattice.measure_energy(), -64.0 - 0.5 * 16.0);
+        assert_eq!(lattice.measure_energy_per_spin(), -4.5);
+    }
+
+    #[test]
+    fn test_get_spin_correlations() {
+        let lattice = Lattice2d::new_basic([6,5]);
+        let corr = lattice.get_spin_correlations();
+        // compare the FFT result with the direct sum
+        for ((dx, dy), &value) in corr.indexed_iter() {
+            let mut direct = 0;
+            for ((x, y), &s) in lattice.nodes.indexed_iter() {
+                direct += s * lattice.nodes[[(x + dx) % 6, (y + dy) % 5]];
+            }
+            assert!((value - direct as f64 / 30.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_structure_factor_and_radial_average() {
+        let lattice = Lattice2d::builder([4, 4])
+            .init_type(InitType::AllUp)
+            .beta(0.5)
+            .seed(1)
+            .build()
+            .unwrap();
+        // all the weight sits in the k = 0 mode
+        let s_k = lattice.get_structure_factor();
+        assert!((s_k[[0,0]] - 16.0).abs() < 1e-9);
+        assert!(s_k.iter().skip(1).all(|s| s.abs() < 1e-9));
+
+        let radial = radial_average(&lattice.get_spin_correlations());
+        // r = 0, 1, sqrt(2), 2, sqrt(5), sqrt(8)
+        assert_eq!(radial.len(), 6);
+        assert_eq!(radial[0].0, 0.0);
+        assert!(radial.iter().all(|(_, g)| (g - 1.0).abs() < 1e-9));
+    }
+
 }
 
 
@@ -2,16 +2,211 @@
 //! estimated over multiple runs, (such as the expected value of the 
 //! magnetization squared) 
 
+use crate::analysis;
+use crate::error::IsingError;
 use crate::lattice2d::*;
-use crate::measurement::Measurement; 
-use std::thread;
+use crate::measurement::{self, Measurement};
+use crate::observable::{self, Observable, ObservableValue};
+use ndarray::prelude::*;
+use rand::Rng;
+use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
 
 /// Parameters for monte carlo sampling
+///
+/// Time is counted in sweeps (see `Lattice2d::sweep`), i.e. n_sites
+/// attempted flips, so the same params suit lattices of any size.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MonteCarloParams {
     pub n_runs: usize,                        // number of dry runs
-    pub flips_to_skip: usize,                 // skip flips for system to cool
+    pub sweeps_to_skip: usize,                // skip sweeps for system to cool
     pub samples_per_run: usize,               // number of samples to make in each run
-    pub flips_to_skip_between_samples: usize, // number of flips to skip between each sample from the same run
+    pub sweeps_between_samples: usize,        // number of sweeps to skip between each sample from the same run
+    pub max_threads: Option<usize>,           // cap on worker threads for the parallel samplers, None for one per CPU
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub start_from_current: bool,             // parallel runs start from the current spins instead of fresh ones
+}
+
+/// Ensemble averaged spatial correlations, see `sample_spatial_correlations`
+pub struct SpatialCorrelations {
+    pub g: Array2<f64>,                 // G(r) = <s_0 s_r> - <s>², indexed by displacement (dx, dy)
+    pub g_radial: Vec<(f64, f64)>,      // radially averaged (r, G(r)), sorted by r
+    pub structure_factor: Array2<f64>,  // <S(k)>, indexed by Fourier mode (k0, k1)
+    pub correlation_length: f64,        // second moment correlation length ξ
+}
+
+/// Autocorrelation functions in time, see `sample_temporal_correlations`
+pub struct TemporalCorrelations {
+    pub times: Vec<f64>,                        // time lags, in sweeps
+    pub spin_autocorrelation: Vec<f64>,         // C(t) = <s_i(0) s_i(t)>
+    pub magnetization_autocorrelation: Vec<f64>, // normalized autocorrelation of the magnetization
+    pub relaxation_time: f64,                   // exponential relaxation time of the magnetization, in sweeps
+}
+
+/// Samples of several observables taken from the same runs,
+/// see `sample_all_metrics`
+pub struct ObservableSamples {
+    pub names: Vec<String>,                     // observable names, in the order they were requested
+    pub values: Vec<Vec<Vec<ObservableValue>>>, // values[observable][run][sample]
+}
+
+impl ObservableSamples {
+    /// Returns the samples of the observable with the given name,
+    /// of dim (n_runs, samples_per_run)
+    pub fn get(&self, name: &str) -> Option<&Vec<Vec<ObservableValue>>> {
+        self.names.iter()
+            .position(|n| n == name)
+            .map(|idx| &self.values[idx])
+    }
+
+    /// Like `get`, for observables with scalar values; returns None if
+    /// there is no such observable or its values are vectors
+    pub fn get_scalar(&self, name: &str) -> Option<Vec<Vec<f64>>> {
+        self.get(name)?
+            .iter()
+            .map(|run| run.iter().map(|v| v.as_scalar()).collect())
+            .collect()
+    }
+}
+
+/// Quantities that `sample_all_metrics` took before it was routed
+/// through the `Observable` trait; every variant is an observable, so
+/// slices of them still work
+#[deprecated(note = "use the observables of the `observable` module, or `observable::from_fn`")]
+pub enum Metric {
+    Energy,                 // total energy E
+    AbsMagnetization,       // |m|, where m is the mean spin
+    MagnetizationSquared,   // m²
+    MagnetizationFourth,    // m⁴, e.g. for the Binder cumulant
+    NeighborCorrelation,    // <s_i s_j> over nearest neighbours, between -1 and +1
+    Custom(String, Box<dyn Fn(&Lattice2d) -> f64>), // a named user-defined quantity
+}
+
+#[allow(deprecated)]
+impl Observable<Lattice2d> for Metric {
+    fn name(&self) -> &str {
+        match self {
+            Metric::Energy => "energy",
+            Metric::AbsMagnetization => "abs_magnetization",
+            Metric::MagnetizationSquared => "magnetization_squared",
+            Metric::MagnetizationFourth => "magnetization_fourth",
+            Metric::NeighborCorrelation => "neighbor_correlation",
+            Metric::Custom(name, _) => name,
+        }
+    }
+
+    fn measure(&self, lattice: &Lattice2d) -> ObservableValue {
+        match self {
+            Metric::Energy => observable::Energy.measure(lattice),
+            Metric::AbsMagnetization => observable::AbsMagnetization.measure(lattice),
+            Metric::MagnetizationSquared => observable::MagnetizationSquared.measure(lattice),
+            Metric::MagnetizationFourth => observable::MagnetizationFourth.measure(lattice),
+            Metric::NeighborCorrelation => observable::NeighborCorrelation.measure(lattice),
+            Metric::Custom(_, f) => f(lattice).into(),
+        }
+    }
+}
+
+/// Samples of several metrics, see `ObservableSamples::get_scalar` for
+/// the values as plain numbers
+#[deprecated(note = "use `ObservableSamples`")]
+pub type MetricSamples = ObservableSamples;
+
+/// Samples a scalar observable, see `sample_energy`
+fn sample_scalar(
+    lattice: &mut Lattice2d,
+    params: &MonteCarloParams,
+    observable: &dyn Observable<Lattice2d>,
+) -> Vec<Vec<f64>> {
+    let mut samples = vec![vec![0.0; params.samples_per_run]; params.n_runs];
+    for run in samples.iter_mut() {
+        lattice.reset_spins();
+        // Time evolve the system to cool (or heat) it
+        lattice.sweep_n(params.sweeps_to_skip);
+        for sample in run.iter_mut() {
+            // Time evolve the system a bit
+            lattice.sweep_n(params.sweeps_between_samples);
+            *sample = observable.measure(lattice).as_slice()[0];
+        }
+    }
+    samples
+}
+
+/// Errors returned by the parallel samplers, kept as a name for the
+/// crate-wide `IsingError`
+pub type MonteCarloError = IsingError;
+
+/// Runs `run(i)` for i in 0..params.n_runs on a pool of at most
+/// params.max_threads threads, collecting the results in order
+///
+/// A panic inside a run is caught and returned as an error, so one
+/// failing run doesn't abort the whole program.
+pub(crate) fn run_parallel<T, F>(params: &MonteCarloParams, run: F) -> Result<Vec<T>, MonteCarloError>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync,
+{
+    let mut builder = rayon::ThreadPoolBuilder::new();
+    if let Some(max_threads) = params.max_threads {
+        builder = builder.num_threads(max_threads);
+    }
+    let pool = builder.build()
+        .map_err(|e| MonteCarloError::ThreadPool(e.to_string()))?;
+    pool.install(|| {
+        (0..params.n_runs)
+            .into_par_iter()
+            .map(|i| {
+                panic::catch_unwind(AssertUnwindSafe(|| run(i))).map_err(|payload| {
+                    let msg = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "unknown panic".to_owned());
+                    MonteCarloError::WorkerPanicked(msg)
+                })
+            })
+            .collect()
+    })
+}
+
+/// Samples a scalar observable with the runs spread over a worker
+/// pool, see `sample_energy_parallel`
+///
+/// The seed of every run is drawn from the lattice's generator before
+/// the pool starts, so a seeded lattice gives the same samples however
+/// many threads there are.
+fn sample_scalar_parallel<O>(
+    lattice: &mut Lattice2d,
+    params: &MonteCarloParams,
+    observable: O,
+) -> Result<Vec<Vec<f64>>, MonteCarloError>
+where
+    O: Observable<Lattice2d> + Sync,
+{
+    let mut rng = lattice.fork_rng();
+    let seeds: Vec<u64> = (0..params.n_runs).map(|_| rng.gen()).collect();
+    let lattice = &*lattice;
+    run_parallel(params, |i| {
+        let mut lattice_copy = if params.start_from_current {
+            // same spins, but a random number stream of its own
+            let mut copy = lattice.clone();
+            copy.seed(seeds[i]);
+            copy
+        } else {
+            // a new lattice with the same input params
+            lattice.fresh_like_seeded(seeds[i])
+        };
+        // Time evolve the system to cool (or heat) it
+        lattice_copy.sweep_n(params.sweeps_to_skip);
+        let mut samples = vec![];
+        for _ in 0..params.samples_per_run {
+            // Time evolve the system a bit
+            lattice_copy.sweep_n(params.sweeps_between_samples);
+            samples.push(observable.measure(&lattice_copy).as_slice()[0]);
+        }
+        samples
+    })
 }
 
 /// The measurement trait samples quantities across lattices and graphs
@@ -19,14 +214,15 @@ pub trait MonteCarlo {
     /// Calculates and returns basic metrics by Monto Carlo sampling
     /// - Energy fluctuations
     /// - Avg Magnetic Susceptibility
-    fn sample_energy_parallel(&mut self, params: &MonteCarloParams) -> Vec<Vec<f64>>;
+    fn sample_energy_parallel(&mut self, params: &MonteCarloParams) -> Result<Vec<Vec<f64>>, MonteCarloError>;
     fn sample_energy(&mut self, params: &MonteCarloParams) -> Vec<Vec<f64>>;
-    fn sample_neighbor_correlations_parallel(&mut self, params: &MonteCarloParams) -> Vec<Vec<f64>>;
+    fn sample_neighbor_correlations_parallel(&mut self, params: &MonteCarloParams) -> Result<Vec<Vec<f64>>, MonteCarloError>;
     fn sample_neighbor_correlations(&mut self, params: &MonteCarloParams) -> Vec<Vec<f64>>;
-    fn sample_magnetization_parallel(&mut self, params: &MonteCarloParams) -> Vec<Vec<f64>>;
+    fn sample_magnetization_parallel(&mut self, params: &MonteCarloParams) -> Result<Vec<Vec<f64>>, MonteCarloError>;
     fn sample_magnetization(&mut self, params: &MonteCarloParams) -> Vec<Vec<f64>>;
-    // TODO: implement below function
-    // fn sample_estimate_all_metrics(&self , params:MonteCarloParams) -> Vec;
+    fn sample_spatial_correlations(&mut self, params: &MonteCarloParams) -> SpatialCorrelations;
+    fn sample_temporal_correlations(&mut self, params: &MonteCarloParams, max_lag: usize) -> TemporalCorrelations;
+    fn sample_all_metrics(&mut self, params: &MonteCarloParams, observables: &[&dyn Observable<Lattice2d>]) -> ObservableSamples;
 }
 
 /// Implements the measurement trait for the Lattice2d type
@@ -63,165 +259,151 @@ impl MonteCarlo for Lattice2d {
     /// Returns a vec of energy samples, of length 
     /// params.n_runs * params.samples_per_run
     fn sample_energy(&mut self, params: &MonteCarloParams) -> Vec<Vec<f64>> {
-        let mut energy = vec![vec![0.0; params.samples_per_run]; params.n_runs];
-        for i in 0..params.n_runs {
-            self.reset_spins();
-            // Time evolve the system to cool (or heat) it
-            self.update_n(params.flips_to_skip);
-            for j in 0..params.samples_per_run {
-                // Time evolve the system a bit
-                self.update_n(params.flips_to_skip_between_samples);
-                energy[i][j] = self.measure_energy();
-            }
-        }
-        return energy;
+        sample_scalar(self, params, &observable::Energy)
     }
 
     /// Monte Carlo sample of energy in parallel
     /// Returns a vec of energy samples, of length 
     /// params.n_runs * params.samples_per_run
-    fn sample_energy_parallel(&mut self, params: &MonteCarloParams) -> Vec<Vec<f64>> {
-        // let mut energy = vec![vec![0.0; params.samples_per_run]; params.n_runs]; // all samples
-        let mut energy = vec![];
-        let mut fetch_handle = vec![];
-        for _ in 0..params.n_runs {
-            // Create a clone: inits new lattice with same input params
-            let mut lattice_copy = self.clone();
-            let flips_to_skip = params.flips_to_skip;
-            let flips_to_skip_between_samples = params.flips_to_skip_between_samples;
-            let samples_per_run = params.samples_per_run;
-            // Time evolve the system to cool (or heat) it
-            fetch_handle.push(thread::spawn(move || -> Vec<f64> {
-                lattice_copy.update_n(flips_to_skip);
-                let mut erg_samples = vec![];
-                for _ in 0..samples_per_run {
-                    // Time evolve the system a bit
-                    lattice_copy.update_n(flips_to_skip_between_samples);
-                    erg_samples.push(lattice_copy.measure_energy());
-                }
-                erg_samples
-            }));
-        }
-        // fetch_handle.into_iter().map(|c| energy.push(c.join().unwrap()));
+    /// Runs are spread over at most params.max_threads threads
+    fn sample_energy_parallel(&mut self, params: &MonteCarloParams) -> Result<Vec<Vec<f64>>, MonteCarloError> {
+        sample_scalar_parallel(self, params, observable::Energy)
+    }
 
-        for thread in fetch_handle.into_iter() {
-            energy.push(thread.join().unwrap());
-        }
-        return energy;
+    /// Monte Carlo estimate of nearest neighbor correlations
+    /// Returns a vec of mean samples, of length params.n_runs
+    fn sample_neighbor_correlations(&mut self, params: &MonteCarloParams) -> Vec<Vec<f64>> {
+        sample_scalar(self, params, &observable::NeighborCorrelation)
     }
 
+    /// Monte Carlo estimate of nearest neighbor correlations
+    /// Returns a vec of mean samples, of length params.n_runs
+    fn sample_neighbor_correlations_parallel(&mut self, params: &MonteCarloParams) -> Result<Vec<Vec<f64>>, MonteCarloError> {
+        sample_scalar_parallel(self, params, observable::NeighborCorrelation)
+    }
 
+    /// Monte Carlo sample the magnetization in parallel
+    /// Returns a vec of mean samples, of length params.n_runs
+    fn sample_magnetization_parallel(&mut self, params: &MonteCarloParams) -> Result<Vec<Vec<f64>>, MonteCarloError> {
+        sample_scalar_parallel(self, params, observable::Magnetization)
+    }
 
-    /// Monte Carlo estimate of nearest neighbor correlations
+    /// Monte Carlo sample the magnetization
     /// Returns a vec of mean samples, of length params.n_runs
-    fn sample_neighbor_correlations(&mut self, params: &MonteCarloParams) -> Vec<Vec<f64>> {
-        // initiate nearest-neigbour correlation vector (empty Vec)
-        let mut nn_corr = vec![vec![0.0; params.samples_per_run]; params.n_runs]; 
-        for i in 0..params.n_runs {
+    fn sample_magnetization(&mut self, params: &MonteCarloParams) -> Vec<Vec<f64>> {
+        sample_scalar(self, params, &observable::Magnetization)
+    }
+
+    /// Monte Carlo estimate of the spatial correlation function
+    /// G(r) = <s_0 s_r> - <s>² for all displacements r, its radial
+    /// average and the second moment correlation length, averaged over
+    /// params.n_runs * params.samples_per_run samples
+    ///
+    /// Each sample costs one FFT of the lattice; the correlations are
+    /// recovered from the averaged structure factor with one inverse FFT
+    fn sample_spatial_correlations(&mut self, params: &MonteCarloParams) -> SpatialCorrelations {
+        let mut structure_factor = Array2::<f64>::zeros(self.nodes.dim());
+        let mut spin_mean = 0.0;
+        for _ in 0..params.n_runs {
             self.reset_spins();
             // Time evolve the system to cool (or heat) it
-            self.update_n(params.flips_to_skip);
-            for j in 0..params.samples_per_run {
+            self.sweep_n(params.sweeps_to_skip);
+            for _ in 0..params.samples_per_run {
                 // Time evolve the system a bit
-                self.update_n(params.flips_to_skip_between_samples);
-                nn_corr[i][j] = self.get_dot_spin_neighbours() as f64 / self.n_sites as f64 / 4.0;
-                // dividing by 4.0 scales it between -1 and +1, since 4 neighbours
+                self.sweep_n(params.sweeps_between_samples);
+                structure_factor += &self.get_structure_factor();
+                spin_mean += self.get_spin_mean();
             }
         }
-        nn_corr
-    }
+        let n_samples = (params.n_runs * params.samples_per_run) as f64;
+        structure_factor /= n_samples;
+        spin_mean /= n_samples;
 
-    /// Monte Carlo estimate of nearest neighbor correlations
-    /// Returns a vec of mean samples, of length params.n_runs
-    fn sample_neighbor_correlations_parallel(&mut self, params: &MonteCarloParams) -> Vec<Vec<f64>> {
-        // initiate nearest neighbour correlation vector (empty Vec)
-        let mut nn_corr = vec![];
-        let mut fetch_handle = vec![];
-        for _ in 0..params.n_runs {
-            // Create a clone: inits new lattice with same input params
-            let mut lattice_copy = self.clone();
-            let flips_to_skip = params.flips_to_skip;
-            let flips_to_skip_between_samples = params.flips_to_skip_between_samples;
-            let samples_per_run = params.samples_per_run;
-            // Time evolve system to cool (or heat) it
-            fetch_handle.push(thread::spawn(move || -> Vec<f64> {
-                lattice_copy.update_n(flips_to_skip);
-                let mut nn_samples = vec![];
-                for _ in 0..samples_per_run {
-                    // Time evolve the system a bit
-                    lattice_copy.update_n(flips_to_skip_between_samples);
-                    nn_samples.push(lattice_copy.get_dot_spin_neighbours() as f64 / lattice_copy.n_sites as f64 / 4.0);
-                }
-                nn_samples
-            }));
-        }
-        for thread in fetch_handle.into_iter() {
-            nn_corr.push(thread.join().unwrap());
+        let g = measurement::correlations_from_structure_factor(&structure_factor)
+            - spin_mean * spin_mean;
+        SpatialCorrelations {
+            g_radial: measurement::radial_average(&g),
+            correlation_length: measurement::second_moment_correlation_length(&structure_factor),
+            g,
+            structure_factor,
         }
-        return nn_corr;
     }
 
-
-    fn sample_magnetization_parallel(&mut self, params: &MonteCarloParams) -> Vec<Vec<f64>> {
-        let mut fetch_handle = vec![];
+    /// Monte Carlo estimate of the spin and magnetization autocorrelation
+    /// functions after the system is settled
+    ///
+    /// Each run records a time series of params.samples_per_run samples,
+    /// params.sweeps_between_samples apart, and the correlations
+    /// are computed for lags 0..=max_lag samples. Time is reported in
+    /// sweeps, so that lattices of different sizes can be compared (e.g.
+    /// with `analysis::dynamic_exponent`).
+    fn sample_temporal_correlations(&mut self, params: &MonteCarloParams, max_lag: usize) -> TemporalCorrelations {
+        let mut spin_corr = vec![0.0; max_lag + 1];
+        let mut counts = vec![0usize; max_lag + 1];
+        let mut magnetization = vec![];
         for _ in 0..params.n_runs {
-            // Create a clone: inits a new lattice with same input params
-            let mut lattice_copy = self.clone();
-            let flips_to_skip = params.flips_to_skip;
-            let flips_to_skip_between_samples = params.flips_to_skip_between_samples;
-            let samples_per_run = params.samples_per_run;
-            // Time evolve system to cool (or heat) it
-            fetch_handle.push(thread::spawn(move || -> Vec<f64> {
-                lattice_copy.update_n(flips_to_skip);
-                let mut mag_samples = vec![];
-                for _ in 0..samples_per_run {
-                    // Time evolve the system a bit
-                    lattice_copy.update_n(flips_to_skip_between_samples);
-                    mag_samples.push(lattice_copy.get_dot_spin_neighbours() as f64 / lattice_copy.n_sites as f64 / 4.0);
+            self.reset_spins();
+            // Time evolve the system to cool (or heat) it
+            self.sweep_n(params.sweeps_to_skip);
+            // most recent configuration first
+            let mut history: VecDeque<Array2<i32>> = VecDeque::with_capacity(max_lag + 1);
+            let mut mag_samples = vec![];
+            for _ in 0..params.samples_per_run {
+                // Time evolve the system a bit
+                self.sweep_n(params.sweeps_between_samples);
+                if history.len() > max_lag {
+                    history.pop_back();
+                }
+                history.push_front(self.nodes.clone());
+                for (lag, past) in history.iter().enumerate() {
+                    let dot: i32 = self.nodes.iter().zip(past.iter()).map(|(s0, s1)| s0 * s1).sum();
+                    spin_corr[lag] += dot as f64 / self.n_sites as f64;
+                    counts[lag] += 1;
                 }
-                mag_samples
-            }));
+                mag_samples.push(self.get_spin_mean());
+            }
+            magnetization.push(mag_samples);
         }
-        let mut magnetization = vec![];
-        for thread in fetch_handle.into_iter() {
-            magnetization.push(thread.join().unwrap());
+
+        let spin_autocorrelation: Vec<f64> = spin_corr.iter()
+            .zip(counts.iter())
+            .map(|(c, &n)| c / n as f64)
+            .collect();
+        let magnetization_autocorrelation = analysis::autocorrelation(&magnetization, max_lag);
+        let times: Vec<f64> = (0..=max_lag)
+            .map(|lag| (lag * params.sweeps_between_samples) as f64)
+            .collect();
+        TemporalCorrelations {
+            relaxation_time: analysis::fit_exponential_decay(&times, &magnetization_autocorrelation),
+            times,
+            spin_autocorrelation,
+            magnetization_autocorrelation,
         }
-        return magnetization;
     }
 
-    /// Monte Carlo sample the magnetization
-    /// Returns a vec of mean samples, of length params.n_runs
-    fn sample_magnetization(&mut self, params: &MonteCarloParams) -> Vec<Vec<f64>> {
-        let mut mag_samples = vec![vec![0.0; params.samples_per_run]; params.n_runs];
-        // Instead, init new lattice object and use multithreding for parallel processing
+    /// Monte Carlo sample of several observables at once
+    /// Every observable is measured on the same samples, so the system
+    /// only has to be equilibrated once per run. Returns the samples of
+    /// each observable, of dim (params.n_runs, params.samples_per_run)
+    fn sample_all_metrics(&mut self, params: &MonteCarloParams, observables: &[&dyn Observable<Lattice2d>]) -> ObservableSamples {
+        let mut values = vec![vec![Vec::with_capacity(params.samples_per_run); params.n_runs]; observables.len()];
         for i in 0..params.n_runs {
             self.reset_spins();
             // Time evolve the system to cool (or heat) it
-            self.update_n(params.flips_to_skip);
-            for j in 0..params.samples_per_run {
+            self.sweep_n(params.sweeps_to_skip);
+            for _ in 0..params.samples_per_run {
                 // Time evolve the system a bit
-                self.update_n(params.flips_to_skip_between_samples);
-                mag_samples[i][j] = self.get_spin_mean();
+                self.sweep_n(params.sweeps_between_samples);
+                for (observable, samples) in observables.iter().zip(values.iter_mut()) {
+                    samples[i].push(observable.measure(self));
+                }
             }
         }
-        mag_samples
+        ObservableSamples {
+            names: observables.iter().map(|o| o.name().to_owned()).collect(),
+            values,
+        }
     }
-
-
-    // TODO: implement the following
-    // (doc) Monte Carlo estimation for spacial correlations after system is settled
-    // (doc) Returns the estimate and uncertainty 1 sigma
-    // fn sample_spacial_correlations(&self , params:MonteCarloParams) -> [f64;3] {
-    //     // initiate nearest neighbo
-    // }
-    // TODO: implement the following
-    // (doc) Monte Carlo estimation for temporal correlations after system is settled
-    // (doc) Returns the estaimate and uncertainty 1 sigma
-    // fn sample_temporal_correlations(&self) -> (f64 , f64);
-    // TODO: implement the following
-    // (doc) Monte Carlo estimagion of all metrics
-    // (doc) Returns a Vec (or dict?) of all the metrics
-    // fn sample_estimate_all_metrics(&self , params:MonteCarloParams) -> Vec;
 }
 
 #[cfg(test)]
@@ -232,9 +414,11 @@ mod test {
     fn test_sample_energy() {
         let params = MonteCarloParams {
             n_runs: 5,
-            flips_to_skip: 1_000, // 1500_000,
+            sweeps_to_skip: 10,
             samples_per_run: 10,
-            flips_to_skip_between_samples: 100,
+            sweeps_between_samples: 1,
+            max_threads: None,
+            start_from_current: false,
         };
         let beta: f64 = 2.4; // roughly critical temp
                              // Initiate a lattice
@@ -256,9 +440,11 @@ mod test {
     fn test_sample_energy_parallel() {
         let params = MonteCarloParams {
             n_runs: 5,
-            flips_to_skip: 1_000,
+            sweeps_to_skip: 10,
             samples_per_run: 10,
-            flips_to_skip_between_samples: 100,
+            sweeps_between_samples: 1,
+            max_threads: None,
+            start_from_current: false,
         };
         let beta: f64 = 2.4;
         let mut lattice = Lattice2d::new(
@@ -270,7 +456,7 @@ mod test {
             0.0f64, // h static field term
             beta,   // 1/TkB
         );
-        let energy_samples: Vec<Vec<f64>> = lattice.sample_energy_parallel(&params);
+        let energy_samples: Vec<Vec<f64>> = lattice.sample_energy_parallel(&params).unwrap();
         assert_eq!(energy_samples.len(), params.n_runs);
         assert_eq!(energy_samples[0].len(), params.samples_per_run);
     }
@@ -279,9 +465,11 @@ mod test {
     fn test_sample_neighbor_correlations() {
         let params = MonteCarloParams {
             n_runs: 5,
-            flips_to_skip: 1_000, // 1500_000,
+            sweeps_to_skip: 10,
             samples_per_run: 10,
-            flips_to_skip_between_samples: 100,
+            sweeps_between_samples: 1,
+            max_threads: None,
+            start_from_current: false,
         };
         let beta: f64 = 2.4; // roughly critical temp
                              // Initiate a lattice
@@ -303,9 +491,11 @@ mod test {
     fn test_neighbor_correlations_parallel() {
         let params = MonteCarloParams {
             n_runs: 5,
-            flips_to_skip: 1_000,
+            sweeps_to_skip: 10,
             samples_per_run: 10,
-            flips_to_skip_between_samples: 100,
+            sweeps_between_samples: 1,
+            max_threads: None,
+            start_from_current: false,
         };
         let beta: f64 = 2.4;
         let mut lattice = Lattice2d::new(
@@ -317,10 +507,200 @@ mod test {
             0.0f64, // h static field term
             beta,   // 1/TkB
         );
-        let nn_corr: Vec<Vec<f64>> = lattice.sample_neighbor_correlations_parallel(&params);
+        let nn_corr: Vec<Vec<f64>> = lattice.sample_neighbor_correlations_parallel(&params).unwrap();
         assert_eq!(nn_corr.len(), params.n_runs);
         assert_eq!(nn_corr[0].len(), params.samples_per_run);
     }
+
+    #[test]
+    fn test_sample_spatial_correlations() {
+        let params = MonteCarloParams {
+            n_runs: 3,
+            sweeps_to_skip: 10,
+            samples_per_run: 10,
+            sweeps_between_samples: 1,
+            max_threads: None,
+            start_from_current: false,
+        };
+        let mut lattice = Lattice2d::builder([8, 8]).beta(0.3).seed(1).build().unwrap();
+        let corr = lattice.sample_spatial_correlations(&params);
+        assert_eq!(corr.g.dim(), (8, 8));
+        // <s_0 s_0> = 1
+        assert_eq!(corr.g_radial[0].0, 0.0);
+        assert!(corr.g_radial[0].1 <= 1.0 + 1e-9);
+        assert!(corr.correlation_length.is_finite());
+    }
+
+    #[test]
+    fn test_sample_temporal_correlations() {
+        let params = MonteCarloParams {
+            n_runs: 2,
+            sweeps_to_skip: 10,
+            samples_per_run: 50,
+            sweeps_between_samples: 1,
+            max_threads: None,
+            start_from_current: false,
+        };
+        let mut lattice = Lattice2d::builder([8, 8]).beta(0.44).seed(1).build().unwrap();
+        let corr = lattice.sample_temporal_correlations(&params, 10);
+        assert_eq!(corr.times.len(), 11);
+        assert_eq!(corr.times[2], 2.0);
+        assert_eq!(corr.spin_autocorrelation[0], 1.0);
+        assert!(corr.spin_autocorrelation.iter().all(|c| c.abs() <= 1.0));
+    }
+
+    #[test]
+    fn test_sample_all_metrics() {
+        let params = MonteCarloParams {
+            n_runs: 3,
+            sweeps_to_skip: 10,
+            samples_per_run: 10,
+            sweeps_between_samples: 1,
+            max_threads: None,
+            start_from_current: false,
+        };
+        let mut lattice = Lattice2d::new_basic([9, 9]);
+        let spin_sum = observable::from_fn("spin_sum", |l: &Lattice2d| l.get_spin_sum() as f64);
+        let radial = observable::RadialSpinCorrelation;
+        let observables: [&dyn Observable<Lattice2d>; 5] = [
+            &observable::Energy,
+            &observable::MagnetizationSquared,
+            &observable::MagnetizationFourth,
+            &spin_sum,
+            &radial,
+        ];
+        let samples = lattice.sample_all_metrics(&params, &observables);
+        assert_eq!(samples.names.len(), 5);
+        let m2 = samples.get_scalar("magnetization_squared").unwrap();
+        let m4 = samples.get_scalar("magnetization_fourth").unwrap();
+        let spin_sum = samples.get_scalar("spin_sum").unwrap();
+        assert!(samples.get_scalar("radial_spin_correlation").is_none());
+        assert_eq!(samples.get("radial_spin_correlation").unwrap()[0].len(), params.samples_per_run);
+        assert_eq!(m2.len(), params.n_runs);
+        assert_eq!(m2[0].len(), params.samples_per_run);
+        // all metrics come from the same configurations
+        for i in 0..params.n_runs {
+            for j in 0..params.samples_per_run {
+                assert!((m2[i][j] * m2[i][j] - m4[i][j]).abs() < 1e-12);
+                assert!(((spin_sum[i][j] / 81.0).powi(2) - m2[i][j]).abs() < 1e-12);
+            }
+        }
+        assert!(samples.get("missing").is_none());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_sample_deprecated_metrics() {
+        let params = MonteCarloParams {
+            n_runs: 2,
+            sweeps_to_skip: 0,
+            samples_per_run: 3,
+            sweeps_between_samples: 0,
+            max_threads: None,
+            start_from_current: false,
+        };
+        let mut lattice = Lattice2d::builder([4, 4]).init_type(InitType::AllUp).build().unwrap();
+        let metrics = [
+            Metric::AbsMagnetization,
+            Metric::Custom("spin_sum".to_owned(), Box::new(|l| l.get_spin_sum() as f64)),
+        ];
+        let observables: Vec<&dyn Observable<Lattice2d>> =
+            metrics.iter().map(|m| m as &dyn Observable<Lattice2d>).collect();
+        let samples: MetricSamples = lattice.sample_all_metrics(&params, &observables);
+        assert_eq!(samples.names, ["abs_magnetization", "spin_sum"]);
+        assert_eq!(samples.get_scalar("spin_sum").unwrap(), vec![vec![16.0; 3]; 2]);
+    }
+
+    #[test]
+    fn test_sample_magnetization_parallel() {
+        let params = MonteCarloParams {
+            n_runs: 3,
+            sweeps_to_skip: 0,
+            samples_per_run: 2,
+            sweeps_between_samples: 0,
+            max_threads: Some(2),
+            start_from_current: false,
+        };
+        let mut lattice = Lattice2d::builder([4, 4])
+            .init_type(InitType::AllUp)
+            .beta(0.5)
+            .seed(1)
+            .build()
+            .unwrap();
+        // without any updates the all-up lattice is fully magnetized
+        let magnetization = lattice.sample_magnetization_parallel(&params).unwrap();
+        assert!(magnetization.iter().flatten().all(|&m| m == 1.0));
+    }
+
+    #[test]
+    fn test_parallel_start_from_current() {
+        let mut params = MonteCarloParams {
+            n_runs: 3,
+            sweeps_to_skip: 0,
+            samples_per_run: 2,
+            sweeps_between_samples: 0,
+            max_threads: Some(2),
+            start_from_current: false,
+        };
+        // fresh lattices start all up, but the current configuration is all down
+        let mut lattice = Lattice2d::builder([4, 4]).init_type(InitType::AllUp).build().unwrap();
+        lattice.set_nodes(-Array2::<i32>::ones([4, 4]));
+        let magnetization = lattice.sample_magnetization_parallel(&params).unwrap();
+        assert!(magnetization.iter().flatten().all(|&m| m == 1.0));
+
+        params.start_from_current = true;
+        let magnetization = lattice.sample_magnetization_parallel(&params).unwrap();
+        assert!(magnetization.iter().flatten().all(|&m| m == -1.0));
+    }
+
+    #[test]
+    fn test_parallel_seeded() {
+        let params = MonteCarloParams {
+            n_runs: 4,
+            sweeps_to_skip: 5,
+            samples_per_run: 3,
+            sweeps_between_samples: 2,
+            max_threads: Some(2),
+            start_from_current: false,
+        };
+        let sample = |params: &MonteCarloParams| {
+            let mut lattice = Lattice2d::builder([8, 8]).seed(5).build().unwrap();
+            lattice.sample_energy_parallel(params).unwrap()
+        };
+        let energy = sample(&params);
+        assert_eq!(energy, sample(&params));
+        // the runs differ from each other, whatever the number of threads
+        assert_ne!(energy[0], energy[1]);
+        assert_eq!(energy, sample(&MonteCarloParams { max_threads: Some(1), ..params }));
+        let params = MonteCarloParams { start_from_current: true, ..params };
+        assert_eq!(sample(&params), sample(&params));
+    }
+
+    #[test]
+    fn test_parallel_worker_panic() {
+        let params = MonteCarloParams {
+            n_runs: 4,
+            sweeps_to_skip: 1,
+            samples_per_run: 2,
+            sweeps_between_samples: 1,
+            max_threads: Some(2),
+            start_from_current: false,
+        };
+        // Glauber updates are not implemented and panic
+        let mut lattice = Lattice2d::new(
+            [4, 4],
+            UpdateRule::Glauber,
+            SpinType::SpinHalf,
+            InitType::Random,
+            1.0f64, // j interaction constant
+            0.0f64, // h static field term
+            0.5f64, // 1/TkB
+        );
+        match lattice.sample_energy_parallel(&params) {
+            Err(MonteCarloError::WorkerPanicked(msg)) => assert!(msg.contains("Glauber")),
+            _ => panic!("expected the worker panic to be returned as an error"),
+        }
+    }
 }
 
 
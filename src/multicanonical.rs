@@ -0,0 +1,262 @@
+//! Multicanonical (and umbrella) sampling adds a bias W(x) to the
+//! Boltzmann weight, over the energy or the magnetization x, so the
+//! lattice samples states that are rare in the canonical ensemble,
+//! e.g. the mixed states between the two phases of a first order
+//! transition. Canonical averages are recovered by reweighting every
+//! sample by 1 / W(x).
+
+use crate::lattice2d::Lattice2d;
+use crate::monte_carlo_measurement::{MonteCarloParams, ObservableSamples};
+use crate::observable::Observable;
+use rand::Rng;
+
+/// The quantity the bias depends on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OrderParameter {
+    Energy,        // the coupling term -J ∑ s_i s_j over pairs, i.e. half of `measure_energy` at h = 0
+    Magnetization, // M = ∑ s_i
+}
+
+/// A lattice sampled with probability ∝ exp(-βE + ln W(x))
+///
+/// ln W is stored per bin of x. For the magnetization the bins are
+/// M = -N, -N + 2, ..., N, for the energy they are the bond sums
+/// ∑ s_i s_j = -2N, -2N + 4, ..., 2N (levels a lattice can't reach
+/// are never visited).
+#[derive(Clone, Debug)]
+pub struct Multicanonical {
+    pub parameter: OrderParameter,
+    pub ln_w: Vec<f64>, // ln W for every bin, n_sites + 1 of them
+}
+
+/// Samples from a biased run, see `Multicanonical::sample`
+pub struct MulticanonicalSamples {
+    pub samples: ObservableSamples,  // the observables, as sampled (not reweighted)
+    pub ln_w: Vec<Vec<f64>>,         // ln W(x) of every sample, [run][sample]
+    pub spin_sums: Vec<Vec<i32>>,    // M = ∑ s_i of every sample, [run][sample]
+    n_sites: usize,
+}
+
+impl Multicanonical {
+    /// Starts from a flat bias, i.e. plain canonical sampling
+    pub fn new(parameter: OrderParameter, n_sites: usize) -> Self {
+        Multicanonical {
+            parameter,
+            ln_w: vec![0.0; n_sites + 1],
+        }
+    }
+
+    /// Uses the given ln W(x), x being the energy (at h = 0, with
+    /// coupling j) or the magnetization M = ∑ s_i
+    pub fn from_fn<F: Fn(f64) -> f64>(parameter: OrderParameter, lattice: &Lattice2d, ln_w: F) -> Self {
+        let n_sites = lattice.n_sites as usize;
+        let ln_w = (0..=n_sites)
+            .map(|bin| match parameter {
+                OrderParameter::Energy => ln_w(-lattice.j * (4.0 * bin as f64 - 2.0 * n_sites as f64)),
+                OrderParameter::Magnetization => ln_w(2.0 * bin as f64 - n_sites as f64),
+            })
+            .collect();
+        Multicanonical { parameter, ln_w }
+    }
+
+    /// The bin of the current configuration; the lattice must be tracking
+    fn bin(&self, lattice: &Lattice2d) -> usize {
+        let n_sites = lattice.n_sites;
+        match self.parameter {
+            OrderParameter::Energy => ((lattice.tracked_bond_sum().unwrap() + 2 * n_sites) / 4) as usize,
+            OrderParameter::Magnetization => ((lattice.tracked_spin_sum().unwrap() + n_sites) / 2) as usize,
+        }
+    }
+
+    /// Sweeps the lattice with the biased Metropolis rule, calling
+    /// `visit(bin)` after every attempted flip. The random numbers come
+    /// from the lattice's generator, so a seeded lattice gives the same run.
+    fn sweep_n<F: FnMut(usize)>(&self, lattice: &mut Lattice2d, n_sweeps: usize, mut visit: F) {
+        assert_eq!(self.ln_w.len(), lattice.n_sites as usize + 1, "Weights don't match the lattice size");
+        if !lattice.is_tracking() {
+            lattice.set_tracking(true);
+        }
+        let mut rng = lattice.fork_rng();
+        let mut bin = self.bin(lattice);
+        for _ in 0..n_sweeps * lattice.n_sites as usize {
+            let idx0 = rng.gen_range(0..lattice.dims[0]);
+            let idx1 = rng.gen_range(0..lattice.dims[1]);
+            let spin = lattice.nodes[[idx0, idx1]];
+            let new_bin = match self.parameter {
+                OrderParameter::Energy => {
+                    (bin as i32 - spin * lattice.neighbour_spin_sum(idx0, idx1) / 2) as usize
+                }
+                OrderParameter::Magnetization => (bin as i32 - spin) as usize,
+            };
+            let log_p = -lattice.beta * lattice.get_dE(idx0, idx1) + self.ln_w[new_bin] - self.ln_w[bin];
+            if log_p >= 0.0 || rng.gen::<f64>() < log_p.exp() {
+                lattice.flip(idx0, idx1);
+                bin = new_bin;
+            }
+            visit(bin);
+        }
+    }
+
+    /// Refines the bias iteratively, W(x) ← W(x) / H(x), H being the
+    /// histogram of a run of sweeps_per_iteration sweeps with the
+    /// current bias. Repeated, this flattens the histogram of x, so the
+    /// runs tunnel freely between the phases.
+    pub fn refine(&mut self, lattice: &mut Lattice2d, n_iterations: usize, sweeps_per_iteration: usize) {
+        for _ in 0..n_iterations {
+            let mut histogram = vec![0usize; self.ln_w.len()];
+            self.sweep_n(lattice, sweeps_per_iteration, |bin| histogram[bin] += 1);
+            for (ln_w, &hits) in self.ln_w.iter_mut().zip(histogram.iter()) {
+                // unvisited bins keep their weight
+                if hits > 0 {
+                    *ln_w -= (hits as f64).ln();
+                }
+            }
+        }
+    }
+
+    /// Samples the observables with the biased rule, resetting the
+    /// lattice before every run, like `MonteCarlo::sample_all_metrics`
+    pub fn sample(
+        &self,
+        lattice: &mut Lattice2d,
+        params: &MonteCarloParams,
+        observables: &[&dyn Observable<Lattice2d>],
+    ) -> MulticanonicalSamples {
+        let mut values = vec![vec![Vec::with_capacity(params.samples_per_run); params.n_runs]; observables.len()];
+        let mut ln_w = vec![Vec::with_capacity(params.samples_per_run); params.n_runs];
+        let mut spin_sums = vec![Vec::with_capacity(params.samples_per_run); params.n_runs];
+        for i in 0..params.n_runs {
+            lattice.reset_spins();
+            self.sweep_n(lattice, params.sweeps_to_skip, |_| ());
+            for _ in 0..params.samples_per_run {
+                self.sweep_n(lattice, params.sweeps_between_samples, |_| ());
+                for (observable, samples) in observables.iter().zip(values.iter_mut()) {
+                    samples[i].push(observable.measure(lattice));
+                }
+                ln_w[i].push(self.ln_w[self.bin(lattice)]);
+                spin_sums[i].push(lattice.tracked_spin_sum().unwrap());
+            }
+        }
+        MulticanonicalSamples {
+            samples: ObservableSamples {
+                names: observables.iter().map(|o| o.name().to_owned()).collect(),
+                values,
+            },
+            ln_w,
+            spin_sums,
+            n_sites: lattice.n_sites as usize,
+        }
+    }
+}
+
+impl MulticanonicalSamples {
+    /// Canonical weights 1 / W(x) of all samples (over all runs),
+    /// normalized to sum to 1
+    fn canonical_weights(&self) -> Vec<f64> {
+        let max = self.ln_w.iter().flatten().fold(f64::NEG_INFINITY, |a, &b| a.max(-b));
+        let weights: Vec<f64> = self.ln_w.iter().flatten().map(|ln_w| (-ln_w - max).exp()).collect();
+        let total: f64 = weights.iter().sum();
+        weights.into_iter().map(|w| w / total).collect()
+    }
+
+    /// Canonical average of a scalar observable, reweighted from the
+    /// biased samples
+    pub fn reweighted_mean(&self, name: &str) -> Option<f64> {
+        let values = self.samples.get_scalar(name)?;
+        Some(
+            values.iter()
+                .flatten()
+                .zip(self.canonical_weights())
+                .map(|(x, w)| x * w)
+                .sum(),
+        )
+    }
+
+    /// Canonical distribution P(m) of the magnetization per spin m = M / N,
+    /// as (m, P(m)) for every possible m
+    pub fn magnetization_distribution(&self) -> Vec<(f64, f64)> {
+        let n = self.n_sites as i32;
+        let mut p = vec![0.0; self.n_sites + 1];
+        for (&m, w) in self.spin_sums.iter().flatten().zip(self.canonical_weights()) {
+            p[((m + n) / 2) as usize] += w;
+        }
+        p.into_iter()
+            .enumerate()
+            .map(|(bin, p)| ((2 * bin as i32 - n) as f64 / n as f64, p))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lattice2d::*;
+    use crate::observable;
+
+    fn params() -> MonteCarloParams {
+        MonteCarloParams {
+            n_runs: 2,
+            sweeps_to_skip: 10,
+            samples_per_run: 2000,
+            sweeps_between_samples: 1,
+            max_threads: None,
+            start_from_current: false,
+        }
+    }
+
+    #[test]
+    fn test_flat_bias_is_canonical() {
+        let mut lattice = Lattice2d::new_basic([4, 4]);
+        let muca = Multicanonical::new(OrderParameter::Energy, 16);
+        let samples = muca.sample(&mut lattice, &params(), &[&observable::Energy]);
+        let energies = samples.samples.get_scalar("energy").unwrap();
+        let mean = energies.iter().flatten().sum::<f64>() / 4000.0;
+        assert!((samples.reweighted_mean("energy").unwrap() - mean).abs() < 1e-9);
+        let total: f64 = samples.magnetization_distribution().iter().map(|(_, p)| p).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_magnetization_bias() {
+        // deep in the ordered phase the canonical runs rarely cross
+        // between m = -1 and m = 1, the biased ones should
+        let mut lattice = Lattice2d::builder([4, 4])
+            .init_type(InitType::AllUp)
+            .beta(0.6)
+            .seed(1)
+            .build()
+            .unwrap();
+        let mut muca = Multicanonical::new(OrderParameter::Magnetization, 16);
+        muca.refine(&mut lattice, 8, 1000);
+        let samples = muca.sample(&mut lattice, &params(), &[&observable::MagnetizationSquared]);
+
+        // the biased histogram is roughly flat
+        let mut histogram = [0usize; 17];
+        for &m in samples.spin_sums.iter().flatten() {
+            histogram[((m + 16) / 2) as usize] += 1;
+        }
+        assert!(histogram.iter().all(|&h| h > 0));
+
+        // exact <m²> and P(m) by enumeration of all 2^16 states
+        let mut weights = [0.0; 17];
+        let mut probe = lattice.clone();
+        for config in 0..1usize << 16 {
+            probe.nodes = ndarray::Array2::from_shape_fn([4, 4], |(i, j)| {
+                if config >> (4 * i + j) & 1 == 1 { 1 } else { -1 }
+            });
+            probe.set_tracking(true);
+            let e = -(probe.tracked_bond_sum().unwrap() as f64);
+            weights[((probe.tracked_spin_sum().unwrap() + 16) / 2) as usize] += (-0.6 * e).exp();
+        }
+        let z: f64 = weights.iter().sum();
+        let exact_m2: f64 = weights.iter()
+            .enumerate()
+            .map(|(bin, w)| w / z * ((2 * bin) as f64 / 16.0 - 1.0).powi(2))
+            .sum();
+        let m2 = samples.reweighted_mean("magnetization_squared").unwrap();
+        assert!((m2 - exact_m2).abs() < 0.05, "<m²>: {} vs {}", m2, exact_m2);
+        let p = samples.magnetization_distribution();
+        assert!((p[0].1 - weights[0] / z).abs() < 0.1);
+        assert!((p[16].1 - weights[16] / z).abs() < 0.1);
+    }
+}
@@ -0,0 +1,259 @@
+//! Observables are named quantities measured on a spin system, which
+//! the MonteCarlo samplers record at every sample. The built-in ones
+//! cover the quantities of the Measurement trait; implement the
+//! Observable trait (or use `from_fn`) to record your own.
+
+use crate::measurement::{self, Measurement};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The value of an observable measured on one sample
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObservableValue {
+    Scalar(f64),
+    Vector(Vec<f64>),
+}
+
+impl ObservableValue {
+    /// Returns the value if it is a scalar
+    pub fn as_scalar(&self) -> Option<f64> {
+        match self {
+            ObservableValue::Scalar(x) => Some(*x),
+            ObservableValue::Vector(_) => None,
+        }
+    }
+
+    /// Returns the value as a slice, a scalar being a slice of length 1
+    pub fn as_slice(&self) -> &[f64] {
+        match self {
+            ObservableValue::Scalar(x) => std::slice::from_ref(x),
+            ObservableValue::Vector(v) => v,
+        }
+    }
+}
+
+impl From<f64> for ObservableValue {
+    fn from(x: f64) -> Self {
+        ObservableValue::Scalar(x)
+    }
+}
+
+impl From<Vec<f64>> for ObservableValue {
+    fn from(v: Vec<f64>) -> Self {
+        ObservableValue::Vector(v)
+    }
+}
+
+/// A named quantity that can be measured on a spin system of type S
+pub trait Observable<S: ?Sized> {
+    fn name(&self) -> &str;                          // name under which samples are stored
+    fn measure(&self, system: &S) -> ObservableValue; // measure on the current state of the system
+}
+
+/// Total energy E
+#[derive(Clone, Copy, Debug)]
+pub struct Energy;
+
+/// Mean spin m = ∑ s_i / n
+#[derive(Clone, Copy, Debug)]
+pub struct Magnetization;
+
+/// |m|
+#[derive(Clone, Copy, Debug)]
+pub struct AbsMagnetization;
+
+/// m²
+#[derive(Clone, Copy, Debug)]
+pub struct MagnetizationSquared;
+
+/// m⁴, e.g. for the Binder cumulant
+#[derive(Clone, Copy, Debug)]
+pub struct MagnetizationFourth;
+
+/// <s_i s_j> over nearest neighbours, between -1 and +1
+#[derive(Clone, Copy, Debug)]
+pub struct NeighborCorrelation;
+
+/// Radially averaged spin correlations C(r), one entry per distinct
+/// distance r (see `measurement::radial_average`)
+#[derive(Clone, Copy, Debug)]
+pub struct RadialSpinCorrelation;
+
+impl<S: Measurement> Observable<S> for Energy {
+    fn name(&self) -> &str {
+        "energy"
+    }
+    fn measure(&self, system: &S) -> ObservableValue {
+        system.measure_energy().into()
+    }
+}
+
+impl<S: Measurement> Observable<S> for Magnetization {
+    fn name(&self) -> &str {
+        "magnetization"
+    }
+    fn measure(&self, system: &S) -> ObservableValue {
+        system.get_spin_mean().into()
+    }
+}
+
+impl<S: Measurement> Observable<S> for AbsMagnetization {
+    fn name(&self) -> &str {
+        "abs_magnetization"
+    }
+    fn measure(&self, system: &S) -> ObservableValue {
+        system.get_spin_mean().abs().into()
+    }
+}
+
+impl<S: Measurement> Observable<S> for MagnetizationSquared {
+    fn name(&self) -> &str {
+        "magnetization_squared"
+    }
+    fn measure(&self, system: &S) -> ObservableValue {
+        system.get_spin_mean().powi(2).into()
+    }
+}
+
+impl<S: Measurement> Observable<S> for MagnetizationFourth {
+    fn name(&self) -> &str {
+        "magnetization_fourth"
+    }
+    fn measure(&self, system: &S) -> ObservableValue {
+        system.get_spin_mean().powi(4).into()
+    }
+}
+
+impl<S: Measurement> Observable<S> for NeighborCorrelation {
+    fn name(&self) -> &str {
+        "neighbor_correlation"
+    }
+    fn measure(&self, system: &S) -> ObservableValue {
+        system.get_neighbour_correlation().into()
+    }
+}
+
+impl<S: Measurement> Observable<S> for RadialSpinCorrelation {
+    fn name(&self) -> &str {
+        "radial_spin_correlation"
+    }
+    fn measure(&self, system: &S) -> ObservableValue {
+        measurement::radial_average(&system.get_spin_correlations())
+            .into_iter()
+            .map(|(_, c)| c)
+            .collect::<Vec<f64>>()
+            .into()
+    }
+}
+
+/// Names of the built-in observables, see `builtin`
+pub const BUILTIN_NAMES: [&str; 7] = [
+    "energy",
+    "magnetization",
+    "abs_magnetization",
+    "magnetization_squared",
+    "magnetization_fourth",
+    "neighbor_correlation",
+    "radial_spin_correlation",
+];
+
+/// Returns the built-in observable with the given name, e.g. to pick
+/// observables from a config file
+pub fn builtin<S: Measurement + 'static>(name: &str) -> Option<&'static (dyn Observable<S> + Sync)> {
+    match name {
+        "energy" => Some(&Energy),
+        "magnetization" => Some(&Magnetization),
+        "abs_magnetization" => Some(&AbsMagnetization),
+        "magnetization_squared" => Some(&MagnetizationSquared),
+        "magnetization_fourth" => Some(&MagnetizationFourth),
+        "neighbor_correlation" => Some(&NeighborCorrelation),
+        "radial_spin_correlation" => Some(&RadialSpinCorrelation),
+        _ => None,
+    }
+}
+
+/// An observable defined by a closure, see `from_fn`
+pub struct FnObservable<F> {
+    name: String,
+    f: F,
+}
+
+/// Creates a named observable from a closure, e.g.
+///
+/// ```
+/// use ising_lib::lattice2d::Lattice2d;
+/// use ising_lib::measurement::Measurement;
+/// use ising_lib::observable::{self, Observable};
+///
+/// let spin_sum = observable::from_fn("spin_sum", |l: &Lattice2d| l.get_spin_sum() as f64);
+/// assert_eq!(spin_sum.name(), "spin_sum");
+/// ```
+pub fn from_fn<S, T, F>(name: &str, f: F) -> FnObservable<F>
+where
+    S: ?Sized,
+    T: Into<ObservableValue>,
+    F: Fn(&S) -> T,
+{
+    FnObservable {
+        name: name.to_owned(),
+        f,
+    }
+}
+
+impl<S, T, F> Observable<S> for FnObservable<F>
+where
+    S: ?Sized,
+    T: Into<ObservableValue>,
+    F: Fn(&S) -> T,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn measure(&self, system: &S) -> ObservableValue {
+        (self.f)(system).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lattice2d::*;
+
+    #[test]
+    fn test_builtin_by_name() {
+        for name in BUILTIN_NAMES {
+            assert_eq!(builtin::<Lattice2d>(name).unwrap().name(), name);
+        }
+        assert!(builtin::<Lattice2d>("susceptibility").is_none());
+    }
+
+    #[test]
+    fn test_builtin_observables() {
+        let lattice = Lattice2d::builder([4, 4])
+            .init_type(InitType::AllUp)
+            .beta(0.5)
+            .seed(1)
+            .build()
+            .unwrap();
+        assert_eq!(Observable::<Lattice2d>::measure(&Magnetization, &lattice), ObservableValue::Scalar(1.0));
+        assert_eq!(Observable::<Lattice2d>::measure(&NeighborCorrelation, &lattice), ObservableValue::Scalar(1.0));
+        assert_eq!(
+            Observable::<Lattice2d>::measure(&Energy, &lattice).as_scalar(),
+            Some(lattice.measure_energy())
+        );
+        let radial = Observable::<Lattice2d>::measure(&RadialSpinCorrelation, &lattice);
+        assert_eq!(radial.as_scalar(), None);
+        assert_eq!(radial.as_slice().len(), 6);
+    }
+
+    #[test]
+    fn test_from_fn() {
+        let lattice = Lattice2d::new_basic([3, 5]);
+        let n_sites = from_fn("n_sites", |l: &Lattice2d| l.n_sites as f64);
+        assert_eq!(n_sites.name(), "n_sites");
+        assert_eq!(n_sites.measure(&lattice), ObservableValue::Scalar(15.0));
+        let spins = from_fn("spins", |l: &Lattice2d| l.nodes.iter().map(|&s| s as f64).collect::<Vec<f64>>());
+        assert_eq!(spins.measure(&lattice).as_slice().len(), 15);
+    }
+}
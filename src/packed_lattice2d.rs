@@ -0,0 +1,396 @@
+//! The bit-packed 2D Spin Lattice Type. It simulates the same model as
+//! Lattice2d, but stores 64 spins per u64 word (multi-spin coding), so
+//! that one sweep handles 64 sites with a few bitwise operations. Use it
+//! for large lattices and long production runs; it implements the
+//! Measurement trait, so the built-in observables work with it too.
+
+use crate::error::IsingError;
+use crate::lattice2d::{InitType, Lattice2d};
+use crate::measurement::{self, Measurement};
+use ndarray::prelude::*;
+use rand::{Rng, RngCore};
+use rand_pcg::Pcg64;
+use rayon::prelude::*;
+
+/// Bits of the sites on even columns of a word
+const EVEN_COLUMNS: u64 = 0x5555_5555_5555_5555;
+
+/// Metropolis acceptance probabilities for every (number of anti-aligned
+/// neighbours k, spin) pair, as 64 bit fixed point fractions
+///
+/// Flipping a spin s with k anti-aligned neighbours costs
+///
+/// ```text
+/// dE = 2J (4 - 2k) + H s
+/// ```
+struct AcceptanceTable {
+    always: [[bool; 2]; 5], // [k][spin up?] the flip is always accepted
+    prob: [[u64; 2]; 5],    // [k][spin up?] acceptance probability * 2^64
+}
+
+impl AcceptanceTable {
+    fn new(j: f64, h: f64, beta: f64) -> Self {
+        let mut table = AcceptanceTable {
+            always: [[false; 2]; 5],
+            prob: [[0; 2]; 5],
+        };
+        for k in 0..5 {
+            for (up, spin) in [(0, -1.0), (1, 1.0)] {
+                #[allow(non_snake_case)]
+                let dE = 2.0 * j * (4.0 - 2.0 * k as f64) + h * spin;
+                let p = (-beta * dE).exp();
+                if p >= 1.0 {
+                    table.always[k][up] = true;
+                } else {
+                    // the cast saturates, and p < 1
+                    table.prob[k][up] = (p * 2f64.powi(64)) as u64;
+                }
+            }
+        }
+        table
+    }
+
+    /// Returns a word with the bits of the flips to accept among
+    /// `candidates`, given the number of anti-aligned neighbours of each
+    /// bit in the bit-sliced counter (c0, c1, c2)
+    ///
+    /// Each bit draws a uniform number U bit by bit, all 64 at once, and
+    /// compares it with the acceptance probability of its class. Most
+    /// bits are decided after a handful of random words.
+    fn accept_mask<R: RngCore>(&self, spins: u64, count: [u64; 3], candidates: u64, rng: &mut R) -> u64 {
+        let [c0, c1, c2] = count;
+        let k_masks = [
+            !c2 & !c1 & !c0,
+            !c2 & !c1 & c0,
+            !c2 & c1 & !c0,
+            !c2 & c1 & c0,
+            c2,
+        ];
+        let mut classes = [(0u64, 0u64); 10]; // (mask, probability)
+        let mut accepted = 0;
+        for (k, k_mask) in k_masks.iter().enumerate() {
+            for (up, spin_mask) in [(0, !spins), (1, spins)] {
+                let mask = k_mask & spin_mask & candidates;
+                if self.always[k][up] {
+                    accepted |= mask;
+                }
+                classes[2 * k + up] = (mask, self.prob[k][up]);
+            }
+        }
+        let mut undecided = candidates & !accepted;
+        for bit in (0..64).rev() {
+            if undecided == 0 {
+                break;
+            }
+            // the current bit of each site's acceptance probability
+            let threshold = classes.iter()
+                .filter(|(_, p)| (p >> bit) & 1 == 1)
+                .fold(0, |t, (mask, _)| t | mask);
+            let uniform = rng.next_u64();
+            // U < p is decided at the first bit where they differ
+            accepted |= undecided & threshold & !uniform;
+            undecided &= !(threshold ^ uniform);
+        }
+        accepted
+    }
+}
+
+/// A 2d spin lattice storing 64 spins per u64 word
+///
+/// Bit b of word w in row i holds the spin at [i, 64 w + b], 1 for up
+/// and 0 for down. The lattice behaves like a torus, like Lattice2d.
+/// dims[1] must be a multiple of 64 and dims[0] must be even.
+#[derive(Clone)]
+pub struct PackedLattice2d {
+    pub dims: [usize; 2],
+    pub n_sites: i32,
+    words: Vec<u64>, // row-major, dims[1] / 64 words per row
+    pub j: f64,    // interaction constant, default 1.0
+    pub h: f64,    // external uniform magnetic field, default 0.0
+    pub beta: f64, // beta = 1/(k_b * T), defaults to 0.43
+    rng: Pcg64,    // source of all randomness in the lattice, see `seed`
+}
+
+impl PackedLattice2d {
+    /// Create a new packed lattice of given dims
+    ///
+    /// Panics if the dims are invalid, see `try_new`.
+    pub fn new(dims: [usize; 2], init_type: InitType, j: f64, h: f64, beta: f64) -> Self {
+        Self::try_new(dims, init_type, j, h, beta).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `new`, but returns an error unless dims[1] is a positive
+    /// multiple of 64 and dims[0] is positive and even
+    pub fn try_new(dims: [usize; 2], init_type: InitType, j: f64, h: f64, beta: f64) -> Result<Self, IsingError> {
+        if !(dims[1].is_multiple_of(64) && dims[0].is_multiple_of(2) && dims[0] > 0 && dims[1] > 0) {
+            return Err(IsingError::InvalidDims(dims));
+        }
+        let mut rng = Pcg64::new(rand::thread_rng().gen(), 0);
+        let n_words = dims[0] * dims[1] / 64;
+        let words = match init_type {
+            InitType::Random => (0..n_words).map(|_| rng.gen()).collect(),
+            InitType::AllUp => vec![!0; n_words],
+        };
+        Ok(PackedLattice2d {
+            dims,
+            n_sites: (dims[0] * dims[1]) as i32,
+            words,
+            j,
+            h,
+            beta,
+            rng,
+        })
+    }
+
+    /// Create a new packed lattice of given dims with randomly generated spins
+    pub fn new_basic(dims: [usize; 2]) -> Self {
+        Self::new(dims, InitType::Random, 1.0, 0.0, 0.43)
+    }
+
+    /// Packs the spins and parameters of a Lattice2d
+    pub fn from_lattice(lattice: &Lattice2d) -> Self {
+        let mut packed = Self::new(lattice.dims, InitType::AllUp, lattice.j, lattice.h, lattice.beta);
+        packed.set_spins(&lattice.nodes);
+        packed
+    }
+
+    /// Reseeds the random number generator of the lattice, see
+    /// `Lattice2d::seed`
+    ///
+    /// Seeded lattices with the same spins and parameters evolve
+    /// identically, which makes runs reproducible and lets them be
+    /// checked against each other.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Pcg64::new(seed as u128, 0);
+    }
+
+    /// Number of words in each row
+    fn words_per_row(&self) -> usize {
+        self.dims[1] / 64
+    }
+
+    /// Returns the spins as an array of -1s and 1s, like `Lattice2d::nodes`
+    pub fn spins(&self) -> Array2<i32> {
+        let words_per_row = self.words_per_row();
+        Array2::from_shape_fn(self.dims, |(idx0, idx1)| {
+            let word = self.words[idx0 * words_per_row + idx1 / 64];
+            if (word >> (idx1 % 64)) & 1 == 1 { 1 } else { -1 }
+        })
+    }
+
+    /// Overwrites the spins from an array of -1s and 1s of the same dims
+    pub fn set_spins(&mut self, spins: &Array2<i32>) {
+        assert_eq!(spins.dim(), (self.dims[0], self.dims[1]));
+        let words_per_row = self.words_per_row();
+        self.words.iter_mut().for_each(|w| *w = 0);
+        for ((idx0, idx1), &s) in spins.indexed_iter() {
+            if s == 1 {
+                self.words[idx0 * words_per_row + idx1 / 64] |= 1 << (idx1 % 64);
+            }
+        }
+    }
+
+    /// Sweep the lattice once (n_sites attempted flips) in checkerboard
+    /// order, updating the rows of each sublattice in parallel
+    ///
+    /// As in `Lattice2d::sweep_checkerboard_parallel`, the neighbours of
+    /// every updated site stay fixed during a half sweep, and each row
+    /// draws from its own PCG stream. The rows are updated in place, in
+    /// one block of rows per thread.
+    pub fn sweep(&mut self) {
+        let table = AcceptanceTable::new(self.j, self.h, self.beta);
+        let words_per_row = self.words_per_row();
+        let n_rows = self.dims[0];
+        let rows_per_block = n_rows.div_ceil(rayon::current_num_threads().min(n_rows));
+        let seed: u128 = self.rng.gen();
+        for colour in 0..2 {
+            // the rows just above and below each block; only their sites
+            // of the other colour are read, which this half sweep leaves alone
+            let row = |idx0: usize| self.words[idx0 * words_per_row..][..words_per_row].to_vec();
+            let halos: Vec<(Vec<u64>, Vec<u64>)> = (0..n_rows)
+                .step_by(rows_per_block)
+                .map(|first| {
+                    let last = (first + rows_per_block).min(n_rows) - 1;
+                    (row((first + n_rows - 1) % n_rows), row((last + 1) % n_rows))
+                })
+                .collect();
+            self.words
+                .par_chunks_mut(rows_per_block * words_per_row)
+                .zip(halos.par_iter())
+                .enumerate()
+                .for_each(|(block, (block_words, (above, below)))| {
+                    let n_block_rows = block_words.len() / words_per_row;
+                    for r in 0..n_block_rows {
+                        let idx0 = block * rows_per_block + r;
+                        let (before, rest) = block_words.split_at_mut(r * words_per_row);
+                        let (this_row, after) = rest.split_at_mut(words_per_row);
+                        let up_row = if r == 0 { &above[..] } else { &before[(r - 1) * words_per_row..] };
+                        let down_row = if r + 1 == n_block_rows { &below[..] } else { &after[..words_per_row] };
+                        let mut rng = Pcg64::new(seed, (colour * n_rows + idx0) as u128);
+                        let candidates = if (idx0 + colour).is_multiple_of(2) { EVEN_COLUMNS } else { !EVEN_COLUMNS };
+                        for w in 0..words_per_row {
+                            // the bits shifted in from the neighbouring words
+                            // are of the other colour, so still unchanged
+                            let spins = this_row[w];
+                            let prev = this_row[(w + words_per_row - 1) % words_per_row];
+                            let next = this_row[(w + 1) % words_per_row];
+                            let left = (spins << 1) | (prev >> 63);
+                            let right = (spins >> 1) | (next << 63);
+                            // anti-aligned neighbours, summed into a bit-sliced counter
+                            let [a1, a2, a3, a4] = [up_row[w], down_row[w], left, right].map(|n| n ^ spins);
+                            let (t1, carry1) = (a1 ^ a2, a1 & a2);
+                            let (t2, carry2) = (a3 ^ a4, a3 & a4);
+                            let carry3 = t1 & t2;
+                            let c0 = t1 ^ t2;
+                            let c1 = carry1 ^ carry2 ^ carry3;
+                            let c2 = (carry1 & carry2) | (carry1 & carry3) | (carry2 & carry3);
+                            this_row[w] = spins ^ table.accept_mask(spins, [c0, c1, c2], candidates, &mut rng);
+                        }
+                    }
+                });
+        }
+    }
+
+    /// Sweep the lattice n times
+    pub fn sweep_n(&mut self, n: usize) {
+        for _ in 0..n {
+            self.sweep();
+        }
+    }
+
+    /// Number of anti-aligned neighbouring pairs, each pair counted once
+    fn count_anti_aligned(&self) -> u32 {
+        let words_per_row = self.words_per_row();
+        let n_rows = self.dims[0];
+        let mut count = 0;
+        for idx0 in 0..n_rows {
+            let row = &self.words[idx0 * words_per_row..][..words_per_row];
+            let down_row = &self.words[((idx0 + 1) % n_rows) * words_per_row..][..words_per_row];
+            for (w, &spins) in row.iter().enumerate() {
+                let right = (spins >> 1) | (row[(w + 1) % words_per_row] << 63);
+                count += (spins ^ right).count_ones() + (spins ^ down_row[w]).count_ones();
+            }
+        }
+        count
+    }
+}
+
+/// Implement the measurement trait for the PackedLattice2d type,
+/// with popcounts in place of sums over the lattice
+impl Measurement for PackedLattice2d {
+    fn get_spin_sum(&self) -> i32 {
+        let n_up: u32 = self.words.iter().map(|w| w.count_ones()).sum();
+        2 * n_up as i32 - self.n_sites
+    }
+
+    fn get_spin_mean(&self) -> f64 {
+        self.get_spin_sum() as f64 / self.n_sites as f64
+    }
+
+    fn _convolve_2d_circ_neighbours(mat: &Array2<i32>) -> Array2<i32> {
+        Lattice2d::_convolve_2d_circ_neighbours(mat)
+    }
+
+    fn get_dot_spin_neighbours(&self) -> i32 {
+        // 2 n pairs, each counted from both ends
+        let bond_sum = 2 * self.n_sites - 2 * self.count_anti_aligned() as i32;
+        2 * bond_sum
+    }
+
+    fn get_neighbour_correlation(&self) -> f64 {
+        self.get_dot_spin_neighbours() as f64 / self.n_sites as f64 / 4.0
+    }
+
+    fn measure_energy(&self) -> f64 {
+        let spin_sum = self.get_spin_sum() as f64;
+        let spin_neighbours_dot = self.get_dot_spin_neighbours() as f64;
+        -self.j * spin_neighbours_dot - self.h * spin_sum
+    }
+
+    fn measure_energy_per_spin(&self) -> f64 {
+        self.measure_energy() / self.n_sites as f64
+    }
+
+    fn get_structure_factor(&self) -> Array2<f64> {
+        measurement::structure_factor(&self.spins())
+    }
+
+    fn get_spin_correlations(&self) -> Array2<f64> {
+        measurement::correlations_from_structure_factor(&self.get_structure_factor())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack() {
+        let lattice = Lattice2d::new_basic([4, 128]);
+        let packed = PackedLattice2d::from_lattice(&lattice);
+        assert_eq!(packed.spins(), lattice.nodes);
+    }
+
+    #[test]
+    fn test_measurements_match_lattice2d() {
+        let lattice = Lattice2d::builder([6, 64]).h(0.3).beta(0.4).seed(1).build().unwrap();
+        let packed = PackedLattice2d::from_lattice(&lattice);
+        assert_eq!(packed.get_spin_sum(), lattice.get_spin_sum());
+        assert_eq!(packed.get_dot_spin_neighbours(), lattice.get_dot_spin_neighbours());
+        assert!((packed.measure_energy() - lattice.measure_energy()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sweep() {
+        // at beta = 0 every flip is accepted
+        let mut packed = PackedLattice2d::new([2, 64], InitType::AllUp, 1.0, 0.0, 0.0);
+        packed.sweep();
+        assert_eq!(packed.get_spin_sum(), -128);
+
+        // at very low temperature the all-up state stays put
+        let mut packed = PackedLattice2d::new([4, 64], InitType::AllUp, 1.0, 0.0, 100.0);
+        packed.sweep_n(3);
+        assert_eq!(packed.get_spin_sum(), 256);
+
+        // high temperature random lattices stay disordered
+        let mut packed = PackedLattice2d::new_basic([8, 128]);
+        packed.beta = 0.1;
+        packed.sweep_n(10);
+        assert!(packed.get_spin_mean().abs() < 0.2);
+    }
+
+    #[test]
+    fn test_try_new_and_seed() {
+        assert_eq!(
+            PackedLattice2d::try_new([4, 100], InitType::AllUp, 1.0, 0.0, 0.4).err(),
+            Some(IsingError::InvalidDims([4, 100]))
+        );
+        assert!(PackedLattice2d::try_new([3, 64], InitType::AllUp, 1.0, 0.0, 0.4).is_err());
+        assert!(PackedLattice2d::try_new([0, 64], InitType::AllUp, 1.0, 0.0, 0.4).is_err());
+
+        let mut packed = PackedLattice2d::new_basic([6, 128]);
+        let mut other = packed.clone();
+        packed.seed(5);
+        other.seed(5);
+        packed.sweep_n(4);
+        other.sweep_n(4);
+        assert_eq!(packed.spins(), other.spins());
+    }
+
+    #[test]
+    fn test_accept_mask() {
+        let table = AcceptanceTable::new(1.0, 0.0, 0.3);
+        let mut rng = Pcg64::new(7, 11);
+        // all spins up with 4 aligned neighbours, accepted with prob exp(-8 beta)
+        let p = (-8.0f64 * 0.3).exp();
+        let n_words = 2000;
+        let accepted: u32 = (0..n_words)
+            .map(|_| table.accept_mask(!0, [0, 0, 0], !0, &mut rng).count_ones())
+            .sum();
+        let fraction = accepted as f64 / (64 * n_words) as f64;
+        assert!((fraction - p).abs() < 0.005);
+        // non-candidates are never flipped, and k = 2 is always accepted
+        assert_eq!(table.accept_mask(!0, [0, 0, 0], 0, &mut rng), 0);
+        assert_eq!(table.accept_mask(!0, [0, !0, 0], EVEN_COLUMNS, &mut rng), EVEN_COLUMNS);
+    }
+}
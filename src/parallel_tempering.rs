@@ -0,0 +1,302 @@
+//! Parallel tempering (replica exchange) runs copies of a system at a
+//! ladder of temperatures, and every now and then swaps the
+//! configurations of neighbouring temperatures. Configurations stuck
+//! in a metastable state at low temperature can then escape by
+//! wandering up the ladder, melting, and cooling down again.
+
+use crate::lattice2d::Lattice2d;
+use crate::measurement::Measurement;
+use crate::monte_carlo_measurement::ObservableSamples;
+use crate::observable::Observable;
+use crate::packed_lattice2d::PackedLattice2d;
+use rand::Rng;
+use rand_pcg::Pcg64;
+use rayon::prelude::*;
+
+/// A spin system that can take part in parallel tempering
+pub trait Replica: Send {
+    /// Inverse temperature
+    fn beta(&self) -> f64;
+    /// Moves the replica to another temperature
+    fn set_beta(&mut self, beta: f64);
+    /// Energy E of the current configuration in the weight exp(-βE) it samples
+    fn energy(&self) -> f64;
+    /// One sweep of updates at the current temperature
+    fn sweep(&mut self);
+}
+
+impl Replica for Lattice2d {
+    fn beta(&self) -> f64 {
+        self.beta
+    }
+    fn set_beta(&mut self, beta: f64) {
+        self.beta = beta;
+    }
+    fn energy(&self) -> f64 {
+        // the updates sample exp(-βE/2), as measure_energy counts
+        // every pair from both ends
+        self.measure_energy() / 2.0
+    }
+    fn sweep(&mut self) {
+        Lattice2d::sweep(self);
+    }
+}
+
+impl Replica for PackedLattice2d {
+    fn beta(&self) -> f64 {
+        self.beta
+    }
+    fn set_beta(&mut self, beta: f64) {
+        self.beta = beta;
+    }
+    fn energy(&self) -> f64 {
+        // the updates sample exp(-βE/2), as measure_energy counts
+        // every pair from both ends
+        self.measure_energy() / 2.0
+    }
+    fn sweep(&mut self) {
+        PackedLattice2d::sweep(self);
+    }
+}
+
+/// A ladder of replicas, replicas[i] being at temperature betas[i]
+///
+/// Swaps are attempted between neighbours on the ladder, alternating
+/// between the even pairs (0, 1), (2, 3), ... and the odd pairs
+/// (1, 2), (3, 4), ...
+pub struct ParallelTempering<R: Replica> {
+    pub replicas: Vec<R>,
+    betas: Vec<f64>,
+    swap_attempts: Vec<usize>, // per neighbouring pair (i, i + 1)
+    swap_accepts: Vec<usize>,  // per neighbouring pair (i, i + 1)
+    odd_pairs: bool,           // which pairs the next exchange attempts
+    rng: Pcg64,                // decides the swaps, see `seed`
+}
+
+impl<R: Replica> ParallelTempering<R> {
+    /// Create a ladder with one replica per beta, made by `make_replica`
+    ///
+    /// The betas should be sorted (either way), so that neighbours on the
+    /// ladder are close in temperature.
+    pub fn new<F: FnMut(f64) -> R>(betas: Vec<f64>, mut make_replica: F) -> Self {
+        assert!(betas.len() >= 2, "Parallel tempering needs at least two temperatures");
+        let replicas = betas.iter()
+            .map(|&beta| {
+                let mut replica = make_replica(beta);
+                replica.set_beta(beta);
+                replica
+            })
+            .collect();
+        let n_pairs = betas.len() - 1;
+        ParallelTempering {
+            replicas,
+            betas,
+            swap_attempts: vec![0; n_pairs],
+            swap_accepts: vec![0; n_pairs],
+            odd_pairs: false,
+            rng: Pcg64::new(rand::thread_rng().gen(), 0),
+        }
+    }
+
+    /// Reseeds the random number generator deciding the swaps
+    ///
+    /// With seeded replicas as well, the whole run is reproducible.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Pcg64::new(seed as u128, 0);
+    }
+
+    /// The temperature ladder
+    pub fn betas(&self) -> &[f64] {
+        &self.betas
+    }
+
+    /// Sweep every replica n_sweeps times, in parallel
+    pub fn sweep_replicas(&mut self, n_sweeps: usize) {
+        self.replicas.par_iter_mut().for_each(|replica| {
+            for _ in 0..n_sweeps {
+                replica.sweep();
+            }
+        });
+    }
+
+    /// Attempts to swap the configurations of neighbouring temperatures,
+    /// for every other pair on the ladder
+    ///
+    /// A swap of replicas at (β_i, E_i) and (β_j, E_j) is accepted with
+    /// probability
+    ///
+    /// ```text
+    /// min(1, exp((β_i - β_j) (E_i - E_j)))
+    /// ```
+    ///
+    /// which keeps the joint distribution of all replicas stationary.
+    pub fn attempt_swaps(&mut self) {
+        let first = if self.odd_pairs { 1 } else { 0 };
+        for i in (first..self.betas.len() - 1).step_by(2) {
+            let delta = (self.betas[i] - self.betas[i + 1])
+                * (self.replicas[i].energy() - self.replicas[i + 1].energy());
+            self.swap_attempts[i] += 1;
+            if delta >= 0.0 || self.rng.gen::<f64>() < delta.exp() {
+                self.replicas.swap(i, i + 1);
+                self.replicas[i].set_beta(self.betas[i]);
+                self.replicas[i + 1].set_beta(self.betas[i + 1]);
+                self.swap_accepts[i] += 1;
+            }
+        }
+        self.odd_pairs = !self.odd_pairs;
+    }
+
+    /// Sweep every replica, then attempt swaps
+    pub fn step(&mut self, sweeps_between_swaps: usize) {
+        self.sweep_replicas(sweeps_between_swaps);
+        self.attempt_swaps();
+    }
+
+    /// Fraction of accepted swaps for every neighbouring pair
+    /// (i, i + 1) since the statistics were last reset. Pairs with no
+    /// attempts give NaN.
+    pub fn swap_rates(&self) -> Vec<f64> {
+        self.swap_accepts.iter()
+            .zip(self.swap_attempts.iter())
+            .map(|(&accepts, &attempts)| accepts as f64 / attempts as f64)
+            .collect()
+    }
+
+    /// Forget the swaps attempted so far
+    pub fn reset_swap_statistics(&mut self) {
+        self.swap_attempts.iter_mut().for_each(|a| *a = 0);
+        self.swap_accepts.iter_mut().for_each(|a| *a = 0);
+    }
+
+    /// Moves the inner temperatures of the ladder towards equal swap
+    /// rates, keeping both ends fixed, and resets the swap statistics
+    ///
+    /// Every gap between neighbouring betas is scaled by the swap rate
+    /// of its pair, so gaps that are hard to cross shrink and easy ones
+    /// grow, then all gaps are rescaled to span the same range as before.
+    /// Repeat with `tune` until the rates settle.
+    pub fn tune_betas(&mut self) {
+        let rates = self.swap_rates();
+        let span = self.betas[self.betas.len() - 1] - self.betas[0];
+        // a small floor keeps pairs that never swapped from collapsing
+        let gaps: Vec<f64> = self.betas.windows(2)
+            .zip(rates.iter())
+            .map(|(b, &rate)| (b[1] - b[0]) * if rate.is_nan() { 1.0 } else { rate.max(0.01) })
+            .collect();
+        let scale = span / gaps.iter().sum::<f64>();
+        for (i, gap) in gaps.iter().enumerate().take(gaps.len() - 1) {
+            self.betas[i + 1] = self.betas[i] + gap * scale;
+        }
+        for (replica, &beta) in self.replicas.iter_mut().zip(self.betas.iter()) {
+            replica.set_beta(beta);
+        }
+        self.reset_swap_statistics();
+    }
+
+    /// Tunes the ladder for n_rounds rounds of steps_per_round steps each,
+    /// see `tune_betas`
+    pub fn tune(&mut self, n_rounds: usize, steps_per_round: usize, sweeps_between_swaps: usize) {
+        for _ in 0..n_rounds {
+            for _ in 0..steps_per_round {
+                self.step(sweeps_between_swaps);
+            }
+            self.tune_betas();
+        }
+    }
+
+    /// Runs the ladder, measuring the observables at every temperature
+    /// after every `sweeps_between_samples` sweeps (and one round of
+    /// swaps). Returns one table per beta, each holding a single run of
+    /// n_samples samples.
+    pub fn sample(
+        &mut self,
+        n_samples: usize,
+        sweeps_between_samples: usize,
+        observables: &[&dyn Observable<R>],
+    ) -> Vec<ObservableSamples> {
+        let names: Vec<String> = observables.iter().map(|o| o.name().to_owned()).collect();
+        let mut tables: Vec<ObservableSamples> = self.betas.iter()
+            .map(|_| ObservableSamples {
+                names: names.clone(),
+                values: vec![vec![Vec::with_capacity(n_samples)]; observables.len()],
+            })
+            .collect();
+        for _ in 0..n_samples {
+            self.step(sweeps_between_samples);
+            for (replica, table) in self.replicas.iter().zip(tables.iter_mut()) {
+                for (observable, values) in observables.iter().zip(table.values.iter_mut()) {
+                    values[0].push(observable.measure(replica));
+                }
+            }
+        }
+        tables
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::observable;
+
+    fn make_lattice(beta: f64) -> Lattice2d {
+        Lattice2d::builder([8, 8]).beta(beta).seed(beta.to_bits()).build().unwrap()
+    }
+
+    #[test]
+    fn test_swaps_at_equal_temperatures() {
+        // with equal betas every swap is accepted
+        let mut pt = ParallelTempering::new(vec![0.4; 4], make_lattice);
+        for _ in 0..10 {
+            pt.step(1);
+        }
+        assert!(pt.swap_rates().iter().all(|&r| r == 1.0));
+        pt.reset_swap_statistics();
+        assert!(pt.swap_rates().iter().all(|r| r.is_nan()));
+    }
+
+    #[test]
+    fn test_seeded_runs_agree() {
+        let run = || {
+            let mut pt = ParallelTempering::new(vec![0.2, 0.4, 0.6], make_lattice);
+            pt.seed(7);
+            for _ in 0..20 {
+                pt.step(1);
+            }
+            let energies: Vec<f64> = pt.replicas.iter().map(|r| r.measure_energy()).collect();
+            (energies, pt.swap_rates())
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn test_sample() {
+        let betas = vec![0.2, 0.3, 0.4, 0.5];
+        let mut pt = ParallelTempering::new(betas.clone(), make_lattice);
+        pt.seed(1);
+        pt.sweep_replicas(20);
+        let tables = pt.sample(50, 1, &[&observable::Energy]);
+        assert_eq!(tables.len(), 4);
+        let mean_energy: Vec<f64> = tables.iter()
+            .map(|t| t.get_scalar("energy").unwrap()[0].iter().sum::<f64>() / 50.0)
+            .collect();
+        assert!(mean_energy[0] > mean_energy[3]);
+        for (replica, &beta) in pt.replicas.iter().zip(betas.iter()) {
+            assert_eq!(replica.beta, beta);
+        }
+        assert!(pt.swap_rates().iter().all(|&r| (0.0..=1.0).contains(&r)));
+    }
+
+    #[test]
+    fn test_tune_betas() {
+        let mut pt = ParallelTempering::new(vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6], make_lattice);
+        pt.seed(1);
+        pt.tune(3, 20, 1);
+        let betas = pt.betas();
+        assert_eq!(betas[0], 0.1);
+        assert!((betas[5] - 0.6).abs() < 1e-12);
+        assert!(betas.windows(2).all(|b| b[1] > b[0]));
+        for (replica, &beta) in pt.replicas.iter().zip(betas.iter()) {
+            assert_eq!(replica.beta, beta);
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Scans of the inverse temperature or the field. Each step sets the
+//! parameter, equilibrates the lattice and averages the observables.
+//! If annealing is on, every step starts from the configuration the
+//! previous one left behind. Cooling gradually then avoids quenched
+//! domains, and cycling the field up and down traces out hysteresis
+//! loops.
+
+use crate::error::IsingError;
+use crate::lattice2d::Lattice2d;
+use crate::observable::Observable;
+
+/// The parameter a sweep scans
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SweepParameter {
+    Beta,
+    Field,
+}
+
+/// Parameters for a parameter sweep, see `run_sweep`
+pub struct SweepParams {
+    pub parameter: SweepParameter,      // what to scan
+    pub values: Vec<f64>,               // values of the parameter, in the order they are visited
+    pub anneal: bool,                   // carry the configuration across steps, otherwise reset the spins at every step
+    pub sweeps_to_skip: usize,          // sweeps to equilibrate at every step
+    pub samples_per_step: usize,        // number of samples at every step
+    pub sweeps_between_samples: usize,  // sweeps between two samples
+}
+
+/// n evenly spaced values from start to end, both included
+pub fn linspace(start: f64, end: f64, n: usize) -> Vec<f64> {
+    match n {
+        0 => vec![],
+        1 => vec![start],
+        _ => (0..n).map(|i| start + (end - start) * i as f64 / (n - 1) as f64).collect(),
+    }
+}
+
+/// The values followed by the same values backwards, without repeating
+/// the turning point, e.g. for a hysteresis loop in the field
+pub fn round_trip(values: &[f64]) -> Vec<f64> {
+    values.iter().chain(values.iter().rev().skip(1)).copied().collect()
+}
+
+/// Averages at one step of a sweep
+#[derive(Clone, Debug)]
+pub struct SweepRow {
+    pub step: usize,       // index of the step, in the order of SweepParams::values
+    pub beta: f64,         // inverse temperature at this step
+    pub h: f64,            // field at this step
+    pub means: Vec<f64>,   // mean of every observable
+    pub errors: Vec<f64>,  // standard error of every mean, ignoring autocorrelation
+}
+
+/// One row per step of a sweep, with the observables as columns
+#[derive(Clone, Debug)]
+pub struct SweepTable {
+    pub names: Vec<String>, // observable names, in the order they were requested
+    pub rows: Vec<SweepRow>,
+}
+
+impl SweepTable {
+    /// Means of the observable with the given name, one per step
+    pub fn column(&self, name: &str) -> Option<Vec<f64>> {
+        let idx = self.names.iter().position(|n| n == name)?;
+        Some(self.rows.iter().map(|row| row.means[idx]).collect())
+    }
+
+    /// Standard errors of the observable with the given name, one per step
+    pub fn errors(&self, name: &str) -> Option<Vec<f64>> {
+        let idx = self.names.iter().position(|n| n == name)?;
+        Some(self.rows.iter().map(|row| row.errors[idx]).collect())
+    }
+}
+
+/// Runs the lattice through every value of the sweep, measuring the
+/// observables (the first entry of vector valued ones) at every step
+///
+/// The lattice is left at the last value, in its last configuration.
+/// Fails if there are fewer than two samples per step, as the standard
+/// errors need at least two.
+pub fn try_run_sweep(
+    lattice: &mut Lattice2d,
+    params: &SweepParams,
+    observables: &[&dyn Observable<Lattice2d>],
+) -> Result<SweepTable, IsingError> {
+    if params.samples_per_step < 2 {
+        return Err(IsingError::TooFewSamples(params.samples_per_step));
+    }
+    let n = params.samples_per_step as f64;
+    let rows = params.values.iter()
+        .enumerate()
+        .map(|(step, &value)| {
+            match params.parameter {
+                SweepParameter::Beta => lattice.beta = value,
+                SweepParameter::Field => lattice.h = value,
+            }
+            if !params.anneal {
+                lattice.reset_spins();
+            }
+            lattice.sweep_n(params.sweeps_to_skip);
+            let mut sums = vec![(0.0, 0.0); observables.len()];
+            for _ in 0..params.samples_per_step {
+                lattice.sweep_n(params.sweeps_between_samples);
+                for (observable, (sum, sum_sq)) in observables.iter().zip(sums.iter_mut()) {
+                    let x = observable.measure(lattice).as_slice()[0];
+                    *sum += x;
+                    *sum_sq += x * x;
+                }
+            }
+            let means: Vec<f64> = sums.iter().map(|(sum, _)| sum / n).collect();
+            let errors = sums.iter()
+                .zip(means.iter())
+                .map(|((_, sum_sq), mean)| ((sum_sq / n - mean * mean).max(0.0) / (n - 1.0)).sqrt())
+                .collect();
+            SweepRow {
+                step,
+                beta: lattice.beta,
+                h: lattice.h,
+                means,
+                errors,
+            }
+        })
+        .collect();
+    Ok(SweepTable {
+        names: observables.iter().map(|o| o.name().to_owned()).collect(),
+        rows,
+    })
+}
+
+/// Like `try_run_sweep`, panics if there are fewer than two samples per step
+pub fn run_sweep(
+    lattice: &mut Lattice2d,
+    params: &SweepParams,
+    observables: &[&dyn Observable<Lattice2d>],
+) -> SweepTable {
+    try_run_sweep(lattice, params, observables).unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lattice2d::*;
+    use crate::observable;
+
+    #[test]
+    fn test_linspace_round_trip() {
+        assert_eq!(linspace(0.0, 1.0, 5), vec![0.0, 0.25, 0.5, 0.75, 1.0]);
+        assert_eq!(linspace(2.0, 3.0, 1), vec![2.0]);
+        assert_eq!(round_trip(&[1.0, 2.0, 3.0]), vec![1.0, 2.0, 3.0, 2.0, 1.0]);
+    }
+
+    #[test]
+    fn test_beta_sweep() {
+        let mut lattice = Lattice2d::builder([8, 8]).seed(1).build().unwrap();
+        let params = SweepParams {
+            parameter: SweepParameter::Beta,
+            values: linspace(0.1, 0.7, 4),
+            anneal: true,
+            sweeps_to_skip: 50,
+            samples_per_step: 20,
+            sweeps_between_samples: 2,
+        };
+        let table = run_sweep(&mut lattice, &params, &[&observable::Energy, &observable::AbsMagnetization]);
+        assert_eq!(table.names, vec!["energy", "abs_magnetization"]);
+        assert_eq!(table.rows.len(), 4);
+        assert_eq!(table.rows[3].beta, 0.7);
+        let energy = table.column("energy").unwrap();
+        assert!(energy[0] > energy[3]);
+        assert!(table.errors("energy").unwrap().iter().all(|&e| e >= 0.0));
+        assert!(table.column("magnetization").is_none());
+    }
+
+    #[test]
+    fn test_too_few_samples() {
+        let mut lattice = Lattice2d::new_basic([4, 4]);
+        let params = SweepParams {
+            parameter: SweepParameter::Beta,
+            values: vec![0.5],
+            anneal: false,
+            sweeps_to_skip: 1,
+            samples_per_step: 1,
+            sweeps_between_samples: 1,
+        };
+        let result = try_run_sweep(&mut lattice, &params, &[&observable::Energy]);
+        assert_eq!(result.unwrap_err(), IsingError::TooFewSamples(1));
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        // deep in the ordered phase the magnetization lags behind the
+        // field, so at h = 0 it remembers where the field came from
+        let mut lattice = Lattice2d::builder([8, 8])
+            .init_type(InitType::AllUp)
+            .beta(0.8)
+            .seed(1)
+            .build()
+            .unwrap();
+        let params = SweepParams {
+            parameter: SweepParameter::Field,
+            values: round_trip(&linspace(-2.0, 2.0, 5)),
+            anneal: true,
+            sweeps_to_skip: 20,
+            samples_per_step: 10,
+            sweeps_between_samples: 1,
+        };
+        let table = run_sweep(&mut lattice, &params, &[&observable::Magnetization]);
+        let m = table.column("magnetization").unwrap();
+        assert_eq!(table.rows[2].h, 0.0);
+        assert_eq!(table.rows[6].h, 0.0);
+        assert!(m[2] < -0.5, "m on the way up: {}", m[2]);
+        assert!(m[6] > 0.5, "m on the way down: {}", m[6]);
+    }
+}
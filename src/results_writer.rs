@@ -0,0 +1,393 @@
+//! Streams samples to a file as the simulation runs, one record per
+//! sample: the parameters of the simulation (e.g. beta and h), the
+//! run and sample index, and the value of every observable. Every
+//! record is flushed once written, so a crash loses at most the record
+//! being written.
+//!
+//! Three formats are supported:
+//!
+//! - CSV with a header line; vector valued observables are written as
+//!   one field of space separated numbers
+//! - JSON Lines, one object per record; vectors are arrays and NaNs
+//!   are written as null
+//! - a simple self-describing binary format, read back with
+//!   `read_binary`. It starts with the magic bytes `ISINGRES`, a
+//!   version byte (1), then the parameter and observable names (each a
+//!   u32 count followed by u32 length prefixed UTF-8 strings). Each record
+//!   follows as the parameters (f64), the run and sample index (u64),
+//!   and for every observable either a 0 byte and an f64 (scalars) or a
+//!   1 byte, a u32 length and that many f64s (vectors). Everything is
+//!   little endian.
+
+use crate::monte_carlo_measurement::ObservableSamples;
+use crate::observable::ObservableValue;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 8] = b"ISINGRES";
+const VERSION: u8 = 1;
+
+/// Format of a results file, see the module docs
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Csv,
+    JsonLines,
+    Binary,
+}
+
+/// Writes one record per sample, see the module docs
+pub struct ResultsWriter<W: Write> {
+    writer: W,
+    format: Format,
+    parameter_names: Vec<String>,
+    observable_names: Vec<String>,
+}
+
+/// One sample read back from a binary results file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub parameters: Vec<f64>,
+    pub run: usize,
+    pub sample: usize,
+    pub values: Vec<ObservableValue>,
+}
+
+/// The contents of a binary results file, see `read_binary`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Results {
+    pub parameter_names: Vec<String>,
+    pub observable_names: Vec<String>,
+    pub records: Vec<Record>,
+}
+
+impl<W: Write> ResultsWriter<W> {
+    /// Starts a results file, writing its header right away
+    pub fn new(writer: W, format: Format, parameter_names: &[&str], observable_names: &[&str]) -> io::Result<Self> {
+        let mut results = ResultsWriter {
+            writer,
+            format,
+            parameter_names: parameter_names.iter().map(|&n| n.to_owned()).collect(),
+            observable_names: observable_names.iter().map(|&n| n.to_owned()).collect(),
+        };
+        match format {
+            Format::Csv => {
+                let columns: Vec<String> = results.parameter_names.iter()
+                    .map(|n| n.as_str())
+                    .chain(["run", "sample"])
+                    .chain(results.observable_names.iter().map(|n| n.as_str()))
+                    .map(csv_field)
+                    .collect();
+                writeln!(results.writer, "{}", columns.join(","))?;
+            }
+            Format::JsonLines => (),
+            Format::Binary => {
+                results.writer.write_all(MAGIC)?;
+                results.writer.write_all(&[VERSION])?;
+                for names in [&results.parameter_names, &results.observable_names] {
+                    results.writer.write_all(&(names.len() as u32).to_le_bytes())?;
+                    for name in names.iter() {
+                        results.writer.write_all(&(name.len() as u32).to_le_bytes())?;
+                        results.writer.write_all(name.as_bytes())?;
+                    }
+                }
+            }
+        }
+        results.writer.flush()?;
+        Ok(results)
+    }
+
+    /// Writes the record of one sample and flushes it
+    pub fn write_record(
+        &mut self,
+        parameters: &[f64],
+        run: usize,
+        sample: usize,
+        values: &[ObservableValue],
+    ) -> io::Result<()> {
+        assert_eq!(parameters.len(), self.parameter_names.len(), "Wrong number of parameters");
+        assert_eq!(values.len(), self.observable_names.len(), "Wrong number of observables");
+        match self.format {
+            Format::Csv => {
+                let fields: Vec<String> = parameters.iter()
+                    .map(|p| p.to_string())
+                    .chain([run.to_string(), sample.to_string()])
+                    .chain(values.iter().map(|v| {
+                        v.as_slice().iter().map(|x| x.to_string()).collect::<Vec<_>>().join(" ")
+                    }))
+                    .collect();
+                writeln!(self.writer, "{}", fields.join(","))?;
+            }
+            Format::JsonLines => {
+                let fields: Vec<String> = self.parameter_names.iter()
+                    .zip(parameters.iter())
+                    .map(|(name, &p)| format!("{}:{}", json_string(name), json_number(p)))
+                    .chain([format!("\"run\":{}", run), format!("\"sample\":{}", sample)])
+                    .chain(self.observable_names.iter().zip(values.iter()).map(|(name, value)| {
+                        let value = match value {
+                            ObservableValue::Scalar(x) => json_number(*x),
+                            ObservableValue::Vector(v) => {
+                                format!("[{}]", v.iter().map(|&x| json_number(x)).collect::<Vec<_>>().join(","))
+                            }
+                        };
+                        format!("{}:{}", json_string(name), value)
+                    }))
+                    .collect();
+                writeln!(self.writer, "{{{}}}", fields.join(","))?;
+            }
+            Format::Binary => {
+                // assemble the record first, so it is written in one go
+                let mut bytes = vec![];
+                parameters.iter().for_each(|p| bytes.extend(p.to_le_bytes()));
+                bytes.extend((run as u64).to_le_bytes());
+                bytes.extend((sample as u64).to_le_bytes());
+                for value in values.iter() {
+                    match value {
+                        ObservableValue::Scalar(x) => {
+                            bytes.push(0);
+                            bytes.extend(x.to_le_bytes());
+                        }
+                        ObservableValue::Vector(v) => {
+                            bytes.push(1);
+                            bytes.extend((v.len() as u32).to_le_bytes());
+                            v.iter().for_each(|x| bytes.extend(x.to_le_bytes()));
+                        }
+                    }
+                }
+                self.writer.write_all(&bytes)?;
+            }
+        }
+        self.writer.flush()
+    }
+
+    /// Writes every sample of e.g. `MonteCarlo::sample_all_metrics`, all
+    /// with the same parameters
+    ///
+    /// The observables must be the ones the writer was created with,
+    /// in the same order.
+    pub fn write_samples(&mut self, parameters: &[f64], samples: &ObservableSamples) -> io::Result<()> {
+        assert_eq!(samples.names, self.observable_names, "Observables differ from the header");
+        let n_runs = samples.values.first().map_or(0, |v| v.len());
+        for run in 0..n_runs {
+            for sample in 0..samples.values[0][run].len() {
+                let values: Vec<ObservableValue> = samples.values.iter().map(|v| v[run][sample].clone()).collect();
+                self.write_record(parameters, run, sample, &values)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Quotes a CSV field if needed
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn json_number(x: f64) -> String {
+    if x.is_finite() {
+        format!("{:?}", x)
+    } else {
+        "null".to_owned()
+    }
+}
+
+/// Reads a binary results file
+///
+/// A record cut short at the end of the file (e.g. by a crash while it
+/// was written) is dropped, all complete records are returned.
+pub fn read_binary<R: Read>(mut reader: R) -> io::Result<Results> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+    let mut magic = [0u8; 9];
+    reader.read_exact(&mut magic)?;
+    if &magic[..8] != MAGIC {
+        return Err(invalid("not a results file"));
+    }
+    if magic[8] != VERSION {
+        return Err(invalid("unsupported results file version"));
+    }
+    let mut names = || -> io::Result<Vec<String>> {
+        let n = read_u32(&mut reader)?;
+        (0..n)
+            .map(|_| {
+                // read through `take` so a corrupt length can't allocate
+                // more than the file holds
+                let len = read_u32(&mut reader)? as usize;
+                let mut name = vec![];
+                (&mut reader).take(len as u64).read_to_end(&mut name)?;
+                if name.len() < len {
+                    return Err(invalid("name length exceeds the file"));
+                }
+                String::from_utf8(name).map_err(|_| invalid("name is not UTF-8"))
+            })
+            .collect()
+    };
+    let parameter_names = names()?;
+    let observable_names = names()?;
+
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+    let mut records = vec![];
+    let mut rest = &data[..];
+    while !rest.is_empty() {
+        match read_record(&mut rest, parameter_names.len(), observable_names.len()) {
+            Ok(record) => records.push(record),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Results {
+        parameter_names,
+        observable_names,
+        records,
+    })
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64<R: Read>(reader: &mut R) -> io::Result<f64> {
+    read_u64(reader).map(f64::from_bits)
+}
+
+fn read_record<R: Read>(reader: &mut R, n_parameters: usize, n_observables: usize) -> io::Result<Record> {
+    let parameters = (0..n_parameters).map(|_| read_f64(reader)).collect::<io::Result<_>>()?;
+    let run = read_u64(reader)? as usize;
+    let sample = read_u64(reader)? as usize;
+    let values = (0..n_observables)
+        .map(|_| {
+            let mut tag = [0u8];
+            reader.read_exact(&mut tag)?;
+            match tag[0] {
+                0 => read_f64(reader).map(ObservableValue::Scalar),
+                1 => {
+                    let len = read_u32(reader)? as usize;
+                    let mut bytes = vec![];
+                    reader.take(len as u64 * 8).read_to_end(&mut bytes)?;
+                    if bytes.len() < len * 8 {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    let values = bytes.chunks_exact(8).map(|b| f64::from_le_bytes(b.try_into().unwrap()));
+                    Ok(ObservableValue::Vector(values.collect()))
+                }
+                _ => Err(io::Error::new(io::ErrorKind::InvalidData, "bad observable tag")),
+            }
+        })
+        .collect::<io::Result<_>>()?;
+    Ok(Record {
+        parameters,
+        run,
+        sample,
+        values,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn values(i: usize) -> Vec<ObservableValue> {
+        vec![ObservableValue::Scalar(-1.5 * i as f64), ObservableValue::Vector(vec![1.0, f64::NAN])]
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut writer = ResultsWriter::new(vec![], Format::Csv, &["beta"], &["energy", "g, radial"]).unwrap();
+        writer.write_record(&[0.4], 0, 1, &values(1)).unwrap();
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(text, "beta,run,sample,energy,\"g, radial\"\n0.4,0,1,-1.5,1 NaN\n");
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let mut writer = ResultsWriter::new(vec![], Format::JsonLines, &["beta", "h"], &["energy", "g"]).unwrap();
+        writer.write_record(&[0.4, 0.0], 2, 3, &values(2)).unwrap();
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(
+            text,
+            "{\"beta\":0.4,\"h\":0.0,\"run\":2,\"sample\":3,\"energy\":-3.0,\"g\":[1.0,null]}\n"
+        );
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let mut writer = ResultsWriter::new(vec![], Format::Binary, &["beta"], &["energy", "g"]).unwrap();
+        let samples = ObservableSamples {
+            names: vec!["energy".to_owned(), "g".to_owned()],
+            values: vec![vec![vec![values(0).remove(0), values(1).remove(0)]], vec![vec![values(0).remove(1); 2]]],
+        };
+        writer.write_samples(&[0.44], &samples).unwrap();
+        let mut bytes = writer.into_inner();
+        let results = read_binary(&bytes[..]).unwrap();
+        assert_eq!(results.parameter_names, vec!["beta"]);
+        assert_eq!(results.observable_names, vec!["energy", "g"]);
+        assert_eq!(results.records.len(), 2);
+        assert_eq!(results.records[1].parameters, vec![0.44]);
+        assert_eq!((results.records[1].run, results.records[1].sample), (0, 1));
+        assert_eq!(results.records[1].values[0], ObservableValue::Scalar(-1.5));
+        assert!(results.records[1].values[1].as_slice()[1].is_nan());
+
+        // a record cut short is dropped
+        bytes.truncate(bytes.len() - 3);
+        assert_eq!(read_binary(&bytes[..]).unwrap().records.len(), 1);
+        assert!(read_binary(&b"not results"[..]).is_err());
+    }
+
+    #[test]
+    fn test_read_binary_truncated() {
+        let mut writer = ResultsWriter::new(vec![], Format::Binary, &["beta"], &["g"]).unwrap();
+        writer.write_record(&[0.44], 0, 0, &values(0)[1..]).unwrap();
+        let bytes = writer.into_inner();
+
+        // a header cut short is an error, not an empty file
+        let header_len = 9 + 4 + 4 + 4 + 4 + 4 + 1;
+        for len in 9..header_len {
+            assert!(read_binary(&bytes[..len]).is_err(), "{}", len);
+        }
+        assert_eq!(read_binary(&bytes[..header_len]).unwrap().records.len(), 0);
+
+        // a name length beyond the end of the file isn't allocated for
+        let mut huge_name = bytes[..13].to_vec();
+        huge_name.extend(u32::MAX.to_le_bytes());
+        let err = read_binary(&huge_name[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // neither is a vector length beyond it; the record counts as cut short
+        let mut huge_vector = bytes[..header_len + 8 + 8 + 8 + 1].to_vec();
+        huge_vector.extend(u32::MAX.to_le_bytes());
+        huge_vector.extend(1.0f64.to_le_bytes());
+        assert_eq!(read_binary(&huge_vector[..]).unwrap().records.len(), 0);
+    }
+}
@@ -0,0 +1,241 @@
+//! Histogram reweighting extrapolates averages measured at one
+//! temperature to nearby ones, since the samples of a run at β0 also
+//! carry information about the distribution at β. With runs at
+//! several temperatures the multiple histogram method (Ferrenberg and
+//! Swendsen, also known as WHAM) combines them, so a handful of runs
+//! gives smooth curves over the whole range they cover.
+//!
+//! The histograms are never binned here: every sample is reweighted
+//! individually, which is the limit of infinitely narrow bins.
+
+use crate::lattice2d::TimeSeries;
+
+/// Samples of one run at inverse temperature beta
+#[derive(Clone, Debug)]
+pub struct Run {
+    pub beta: f64,
+    pub energy: Vec<f64>,     // energy E of every sample, in the weight exp(-βE) the run samples
+    pub observable: Vec<f64>, // the observable to reweight, one value per sample
+}
+
+impl Run {
+    /// A run with the magnetization as its observable
+    ///
+    /// The energies of the series are halved: the Metropolis updates
+    /// sample exp(-βE/2), as `measure_energy` counts every pair of
+    /// neighbours from both ends.
+    pub fn from_time_series(beta: f64, series: &TimeSeries) -> Self {
+        Run {
+            beta,
+            energy: series.energy.iter().map(|e| e / 2.0).collect(),
+            observable: series.magnetization.clone(),
+        }
+    }
+}
+
+/// Runs combined with the multiple histogram equations, see `new`
+#[derive(Clone, Debug)]
+pub struct Reweighting {
+    runs: Vec<Run>,
+    ln_z: Vec<f64>,   // ln Z of every run, up to a common constant
+    ln_den: Vec<f64>, // ln ∑_i n_i exp(-β_i E_k) / Z_i for every sample k, over all runs
+}
+
+impl Reweighting {
+    /// Solves the multiple histogram equations
+    ///
+    /// ```text
+    /// Z_j = ∑_k exp(-β_j E_k) / ∑_i n_i exp(-β_i E_k) / Z_i
+    /// ```
+    ///
+    /// (k running over the samples of all runs, n_i being the number of
+    /// samples of run i) by iteration. With a single run this reduces to
+    /// single histogram reweighting.
+    pub fn new(runs: Vec<Run>) -> Self {
+        assert!(!runs.is_empty(), "Reweighting needs at least one run");
+        for run in runs.iter() {
+            assert!(!run.energy.is_empty(), "Runs must have samples");
+            assert_eq!(run.energy.len(), run.observable.len(), "Energy and observable series differ in length");
+        }
+        let mut reweighting = Reweighting {
+            ln_z: vec![0.0; runs.len()],
+            ln_den: vec![],
+            runs,
+        };
+        for _ in 0..10_000 {
+            reweighting.update_denominators();
+            let mut ln_z: Vec<f64> = reweighting.runs.iter()
+                .map(|run| reweighting.ln_partition_function(run.beta))
+                .collect();
+            // Z is only fixed up to a constant
+            let shift = ln_z[0];
+            ln_z.iter_mut().for_each(|x| *x -= shift);
+            let change = ln_z.iter()
+                .zip(reweighting.ln_z.iter())
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f64::max);
+            reweighting.ln_z = ln_z;
+            if change < 1e-10 {
+                break;
+            }
+        }
+        reweighting.update_denominators();
+        reweighting
+    }
+
+    /// ln Z of every run, relative to the first one
+    pub fn ln_partition_functions(&self) -> &[f64] {
+        &self.ln_z
+    }
+
+    /// Recomputes ln_den from the current ln_z
+    fn update_denominators(&mut self) {
+        let ln_n: Vec<f64> = self.runs.iter().map(|run| (run.energy.len() as f64).ln()).collect();
+        self.ln_den = self.runs.iter()
+            .flat_map(|run| run.energy.iter())
+            .map(|&e| {
+                log_sum_exp(self.runs.iter()
+                    .zip(ln_n.iter().zip(self.ln_z.iter()))
+                    .map(|(run, (ln_n, ln_z))| ln_n - run.beta * e - ln_z))
+            })
+            .collect();
+    }
+
+    /// ln Z(beta) = ln ∑_k exp(-β E_k) / ∑_i n_i exp(-β_i E_k) / Z_i
+    fn ln_partition_function(&self, beta: f64) -> f64 {
+        log_sum_exp(self.runs.iter()
+            .flat_map(|run| run.energy.iter())
+            .zip(self.ln_den.iter())
+            .map(|(&e, ln_den)| -beta * e - ln_den))
+    }
+
+    /// Canonical average <f(E, O)> at beta
+    pub fn expectation<F: Fn(f64, f64) -> f64>(&self, beta: f64, f: F) -> f64 {
+        let samples = self.runs.iter()
+            .flat_map(|run| run.energy.iter().zip(run.observable.iter()))
+            .zip(self.ln_den.iter());
+        let ln_w: Vec<f64> = samples.clone().map(|((&e, _), ln_den)| -beta * e - ln_den).collect();
+        let max = ln_w.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let (sum, norm) = samples.zip(ln_w.iter())
+            .fold((0.0, 0.0), |(sum, norm), (((&e, &o), _), ln_w)| {
+                let w = (ln_w - max).exp();
+                (sum + w * f(e, o), norm + w)
+            });
+        sum / norm
+    }
+
+    /// <O> at beta
+    pub fn mean(&self, beta: f64) -> f64 {
+        self.expectation(beta, |_, o| o)
+    }
+
+    /// <E> at beta
+    pub fn mean_energy(&self, beta: f64) -> f64 {
+        self.expectation(beta, |e, _| e)
+    }
+
+    /// Specific heat of the whole system C = β² (<E²> - <E>²) at beta
+    pub fn specific_heat(&self, beta: f64) -> f64 {
+        let mean = self.mean_energy(beta);
+        beta.powi(2) * self.expectation(beta, |e, _| (e - mean).powi(2))
+    }
+
+    /// Jackknife estimate of the error of any quantity computed from the
+    /// reweighted runs, e.g. `|r| r.specific_heat(0.44)`
+    ///
+    /// Every run is cut into n_blocks blocks, and the quantity is
+    /// recomputed n_blocks times, each time leaving out one block of
+    /// every run. Blocks should be longer than the autocorrelation time.
+    /// Returns (estimate, error).
+    pub fn jackknife<F: Fn(&Reweighting) -> f64>(&self, n_blocks: usize, estimator: F) -> (f64, f64) {
+        assert!(n_blocks >= 2, "The jackknife needs at least two blocks");
+        let estimates: Vec<f64> = (0..n_blocks)
+            .map(|block| {
+                let runs = self.runs.iter()
+                    .map(|run| {
+                        let n = run.energy.len();
+                        let (start, end) = (block * n / n_blocks, (block + 1) * n / n_blocks);
+                        let leave_out = |v: &[f64]| [&v[..start], &v[end..]].concat();
+                        Run {
+                            beta: run.beta,
+                            energy: leave_out(&run.energy),
+                            observable: leave_out(&run.observable),
+                        }
+                    })
+                    .collect();
+                estimator(&Reweighting::new(runs))
+            })
+            .collect();
+        let mean = estimates.iter().sum::<f64>() / n_blocks as f64;
+        let variance = estimates.iter().map(|x| (x - mean).powi(2)).sum::<f64>()
+            * (n_blocks - 1) as f64 / n_blocks as f64;
+        (estimator(self), variance.sqrt())
+    }
+
+    /// <O> at beta, with its jackknife error
+    pub fn mean_with_error(&self, beta: f64, n_blocks: usize) -> (f64, f64) {
+        self.jackknife(n_blocks, |r| r.mean(beta))
+    }
+}
+
+/// ln ∑ exp(x_i), without overflow
+fn log_sum_exp<I: Iterator<Item = f64>>(xs: I) -> f64 {
+    let xs: Vec<f64> = xs.collect();
+    let max = xs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    max + xs.iter().map(|x| (x - max).exp()).sum::<f64>().ln()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::exact;
+    use crate::lattice2d::*;
+
+    fn record(beta: f64, n_sweeps: usize) -> Run {
+        let mut lattice = Lattice2d::builder([4, 4])
+            .init_type(InitType::AllUp)
+            .beta(beta)
+            .seed(1)
+            .build()
+            .unwrap();
+        lattice.sweep_n(100);
+        let series = lattice.record_time_series(n_sweeps);
+        Run::from_time_series(beta, &series)
+    }
+
+    #[test]
+    fn test_single_histogram() {
+        let run = Run {
+            beta: 0.5,
+            energy: vec![-2.0, 0.0, 2.0, 0.0],
+            observable: vec![1.0, 2.0, 3.0, 4.0],
+        };
+        let reweighting = Reweighting::new(vec![run]);
+        // at the simulated temperature, plain averages
+        assert!((reweighting.mean(0.5) - 2.5).abs() < 1e-12);
+        // elsewhere, samples weighted by exp(-(β - β0) E)
+        let w = [0.2f64.exp(), 1.0, (-0.2f64).exp(), 1.0];
+        let expected = (w[0] + 2.0 * w[1] + 3.0 * w[2] + 4.0 * w[3]) / w.iter().sum::<f64>();
+        assert!((reweighting.mean(0.6) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_multi_histogram() {
+        let reweighting = Reweighting::new(vec![record(0.3, 3000), record(0.5, 3000)]);
+        // Z(0.5) / Z(0.3) from the exact density of states
+        let dos = exact::density_of_states([4, 4], 1.0);
+        let exact_ln_z = dos.ln_partition_function(0.5) - dos.ln_partition_function(0.3);
+        let ln_z = reweighting.ln_partition_functions()[1];
+        assert!((ln_z - exact_ln_z).abs() < 0.1 * exact_ln_z.abs(), "ln Z: {} vs {}", ln_z, exact_ln_z);
+
+        for &beta in [0.3, 0.4, 0.5].iter() {
+            let (e, exact_e) = (reweighting.mean_energy(beta), dos.internal_energy(beta));
+            assert!((e - exact_e).abs() < 0.08 * exact_e.abs(), "E({}): {} vs {}", beta, e, exact_e);
+        }
+        let (c, error) = reweighting.jackknife(5, |r| r.specific_heat(0.4));
+        assert!(error > 0.0);
+        assert!((c - dos.specific_heat(0.4)).abs() < 0.2 * c);
+        let (m, _) = reweighting.mean_with_error(0.4, 5);
+        assert!(m.abs() <= 1.0);
+    }
+}
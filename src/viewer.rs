@@ -0,0 +1,275 @@
+//! A live view of a running lattice in the terminal. Every frame is
+//! redrawn in place by moving the cursor back to the top left corner,
+//! so the terminal doesn't scroll. Each character cell shows two rows
+//! of the lattice with the half block `▀`, coloured by a colour map (the
+//! top site as the foreground, the bottom one as the background), or
+//! with plain block characters if colour is off. Below the lattice are
+//! the energy per spin, the magnetization and the acceptance rate.
+//!
+//! With the `viewer` feature, `Viewer::run` runs the simulation
+//! interactively: ↑/↓ change β, ←/→ change h, space pauses, r resets
+//! the spins and q or Esc quits.
+
+use crate::image_export::ColorMap;
+use crate::lattice2d::Lattice2d;
+use crate::measurement::Measurement;
+#[cfg(feature = "viewer")]
+use std::io::{self, Write};
+#[cfg(feature = "viewer")]
+use std::time::{Duration, Instant};
+
+/// Keys understood by `Viewer::handle_key`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Char(char),
+    Esc,
+}
+
+/// An interactive view of a lattice, see the module docs
+pub struct Viewer {
+    pub lattice: Lattice2d,
+    pub color_map: Option<ColorMap>, // colours of the sites, plain characters if None
+    pub sweeps_per_frame: usize,     // sweeps between two frames
+    pub beta_step: f64,              // change of beta on ↑/↓
+    pub h_step: f64,                 // change of h on ←/→
+    pub paused: bool,
+    sweeps: usize, // sweeps done so far
+}
+
+impl Viewer {
+    /// A view of the lattice in colour, sweeping it once per frame
+    pub fn new(mut lattice: Lattice2d) -> Self {
+        // the statistics are read every frame, keep them cheap
+        lattice.set_tracking(true);
+        Viewer {
+            lattice,
+            color_map: Some(ColorMap::BlueRed),
+            sweeps_per_frame: 1,
+            beta_step: 0.01,
+            h_step: 0.05,
+            paused: false,
+            sweeps: 0,
+        }
+    }
+
+    /// Sweeps done so far
+    pub fn sweeps(&self) -> usize {
+        self.sweeps
+    }
+
+    /// Advances the simulation by one frame, unless paused
+    pub fn step(&mut self) {
+        if !self.paused {
+            self.lattice.sweep_n(self.sweeps_per_frame);
+            self.sweeps += self.sweeps_per_frame;
+        }
+    }
+
+    /// Reacts to a key press, returns false if the viewer should quit
+    pub fn handle_key(&mut self, key: Key) -> bool {
+        match key {
+            Key::Up => self.lattice.beta += self.beta_step,
+            Key::Down => self.lattice.beta = (self.lattice.beta - self.beta_step).max(0.0),
+            Key::Right => self.lattice.h += self.h_step,
+            Key::Left => self.lattice.h -= self.h_step,
+            Key::Char(' ') => self.paused = !self.paused,
+            Key::Char('r') => {
+                self.lattice.reset_spins();
+                self.lattice.set_tracking(true);
+                self.sweeps = 0;
+            }
+            Key::Char('q') | Key::Esc => return false,
+            _ => (),
+        }
+        true
+    }
+
+    /// The whole frame as text with ANSI escape codes, starting with a
+    /// move of the cursor to the top left corner and clearing what is
+    /// left of every line, with lines ending in "\r\n" as terminals in
+    /// raw mode expect
+    pub fn render(&self) -> String {
+        let mut frame = String::from("\x1b[H");
+        let [height, width] = self.lattice.dims;
+        let nodes = &self.lattice.nodes;
+        for idx0 in (0..height).step_by(2) {
+            for idx1 in 0..width {
+                let top = nodes[[idx0, idx1]];
+                // an odd number of rows leaves the bottom half of the last line empty
+                let bottom = (idx0 + 1 < height).then(|| nodes[[idx0 + 1, idx1]]);
+                match &self.color_map {
+                    Some(color_map) => {
+                        let [r, g, b] = color_map.color(top as f64);
+                        frame += &format!("\x1b[38;2;{};{};{}m", r, g, b);
+                        match bottom {
+                            Some(bottom) => {
+                                let [r, g, b] = color_map.color(bottom as f64);
+                                frame += &format!("\x1b[48;2;{};{};{}m", r, g, b);
+                            }
+                            None => frame += "\x1b[49m",
+                        }
+                        frame.push('▀');
+                    }
+                    None => frame.push(match (top > 0, bottom.is_some_and(|s| s > 0)) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    }),
+                }
+            }
+            if self.color_map.is_some() {
+                frame += "\x1b[0m";
+            }
+            frame += "\x1b[K\r\n";
+        }
+        frame += &format!(
+            "β = {:.3}  h = {:.3}  sweeps = {}{}\x1b[K\r\n",
+            self.lattice.beta,
+            self.lattice.h,
+            self.sweeps,
+            if self.paused { "  (paused)" } else { "" },
+        );
+        frame += &format!(
+            "E/N = {:.4}  m = {:.4}  acceptance = {:.4}\x1b[K\r\n",
+            self.lattice.measure_energy_per_spin(),
+            self.lattice.get_spin_mean(),
+            self.lattice.acceptance_rate(),
+        );
+        frame += "↑/↓ β  ←/→ h  space pause  r reset  q quit\x1b[K\r\n";
+        frame
+    }
+
+    /// Runs the simulation in the terminal until q or Esc is pressed,
+    /// drawing at most frames_per_second frames per second
+    ///
+    /// Switches to the alternate screen in raw mode and restores the
+    /// terminal on return, also on errors and panics.
+    #[cfg(feature = "viewer")]
+    pub fn run(&mut self, frames_per_second: f64) -> io::Result<()> {
+        let _screen = RawScreen::enter()?;
+        self.run_loop(&mut io::stdout(), Duration::from_secs_f64(1.0 / frames_per_second))
+    }
+
+    #[cfg(feature = "viewer")]
+    fn run_loop<W: Write>(&mut self, out: &mut W, frame_time: Duration) -> io::Result<()> {
+        use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+
+        loop {
+            let start = Instant::now();
+            self.step();
+            out.write_all(self.render().as_bytes())?;
+            out.flush()?;
+            // handle the keys pressed until the next frame is due
+            loop {
+                let timeout = frame_time.saturating_sub(start.elapsed());
+                if !event::poll(timeout)? {
+                    break;
+                }
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Release {
+                        continue;
+                    }
+                    // raw mode turns off SIGINT, so Ctrl-C has to quit here;
+                    // other Ctrl chords aren't bound to anything
+                    if key.modifiers.contains(KeyModifiers::CONTROL) {
+                        if key.code == KeyCode::Char('c') {
+                            return Ok(());
+                        }
+                        continue;
+                    }
+                    let key = match key.code {
+                        KeyCode::Up => Key::Up,
+                        KeyCode::Down => Key::Down,
+                        KeyCode::Left => Key::Left,
+                        KeyCode::Right => Key::Right,
+                        KeyCode::Esc => Key::Esc,
+                        KeyCode::Char(c) => Key::Char(c),
+                        _ => continue,
+                    };
+                    if !self.handle_key(key) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The alternate screen in raw mode, restoring the terminal when dropped
+#[cfg(feature = "viewer")]
+struct RawScreen;
+
+#[cfg(feature = "viewer")]
+impl RawScreen {
+    fn enter() -> io::Result<Self> {
+        crossterm::terminal::enable_raw_mode()?;
+        // from here on the drop undoes whatever was switched on
+        let screen = RawScreen;
+        let mut stdout = io::stdout();
+        write!(stdout, "\x1b[?1049h\x1b[?25l\x1b[2J")?;
+        stdout.flush()?;
+        Ok(screen)
+    }
+}
+
+#[cfg(feature = "viewer")]
+impl Drop for RawScreen {
+    fn drop(&mut self) {
+        // nothing to report the errors to, restore as much as possible
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = stdout.flush();
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ndarray::prelude::*;
+
+    #[test]
+    fn test_render_half_blocks() {
+        let mut lattice = Lattice2d::new_basic([3, 2]);
+        lattice.set_nodes(array![[1, -1], [1, 1], [-1, 1]]);
+        let mut viewer = Viewer::new(lattice);
+        viewer.color_map = None;
+        let frame = viewer.render();
+        let lines: Vec<&str> = frame.split("\r\n").collect();
+        assert_eq!(lines[0], "\x1b[H█▄\x1b[K");
+        assert_eq!(lines[1], " ▀\x1b[K");
+        assert!(lines[2].starts_with("β = 0.430  h = 0.000  sweeps = 0"));
+        let energy = viewer.lattice.measure_energy_per_spin();
+        assert!(lines[3].starts_with(&format!("E/N = {:.4}  m = 0.3333", energy)));
+
+        viewer.color_map = Some(ColorMap::BlueRed);
+        let frame = viewer.render();
+        assert!(frame.starts_with("\x1b[H\x1b[38;2;255;0;0m\x1b[48;2;255;0;0m▀"));
+        // the bottom half of the last line is left empty
+        assert!(frame.contains("\x1b[38;2;0;0;255m\x1b[49m▀"));
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut viewer = Viewer::new(Lattice2d::new_basic([4, 4]));
+        assert!(viewer.handle_key(Key::Up));
+        assert!((viewer.lattice.beta - 0.44).abs() < 1e-12);
+        viewer.handle_key(Key::Left);
+        assert!((viewer.lattice.h + 0.05).abs() < 1e-12);
+        viewer.handle_key(Key::Char(' '));
+        viewer.step();
+        assert_eq!(viewer.sweeps(), 0);
+        viewer.handle_key(Key::Char(' '));
+        viewer.step();
+        assert_eq!(viewer.sweeps(), 1);
+        viewer.handle_key(Key::Char('r'));
+        assert_eq!(viewer.sweeps(), 0);
+        assert!(!viewer.handle_key(Key::Char('q')));
+        assert!(!viewer.handle_key(Key::Esc));
+    }
+}
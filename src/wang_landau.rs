@@ -0,0 +1,227 @@
+//! Wang–Landau sampling estimates the density of states g(E) of the
+//! lattice directly, by a random walk in energy that is pushed away
+//! from levels it has already visited. Once g(E) is known, the
+//! thermodynamics at any temperature follows without further sampling.
+
+use crate::lattice2d::Lattice2d;
+use rand::Rng;
+
+/// How the modification factor f is reduced, see `WangLandauParams`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ModificationSchedule {
+    /// ln f is halved every time the histogram is flat (the original
+    /// Wang–Landau scheme)
+    Halve,
+    /// ln f is halved on flat histograms until it drops below 1/t, then
+    /// follows ln f = 1/t, t being the time in sweeps (Belardinelli and
+    /// Pereyra), which avoids the saturation of the error of `Halve`
+    OneOverT,
+}
+
+/// Parameters for Wang–Landau sampling
+pub struct WangLandauParams {
+    pub flatness: f64,                   // histogram is flat once min H(E) >= flatness * mean H(E)
+    pub ln_f_initial: f64,               // initial ln f, usually 1
+    pub ln_f_final: f64,                 // stop once ln f drops below this
+    pub schedule: ModificationSchedule,  // how ln f is reduced
+    pub sweeps_between_checks: usize,    // sweeps between two checks of the flatness
+}
+
+impl Default for WangLandauParams {
+    fn default() -> Self {
+        WangLandauParams {
+            flatness: 0.8,
+            ln_f_initial: 1.0,
+            ln_f_final: 1e-6,
+            schedule: ModificationSchedule::Halve,
+            sweeps_between_checks: 100,
+        }
+    }
+}
+
+/// Density of states g(E) over the discrete energy levels of a
+/// system, normalized so that ∑ g(E) is the total number of states
+#[derive(Clone, Debug)]
+pub struct DensityOfStates {
+    pub energies: Vec<f64>, // energy levels, sorted
+    pub ln_g: Vec<f64>,     // ln g(E) for every level
+}
+
+impl DensityOfStates {
+    /// Creates a density of states from levels and ln g(E) known up to
+    /// a constant, normalizing it so that ∑ g(E) = exp(ln_n_states)
+    pub fn new(energies: Vec<f64>, ln_g: Vec<f64>, ln_n_states: f64) -> Self {
+        assert_eq!(energies.len(), ln_g.len());
+        let mut levels: Vec<(f64, f64)> = energies.into_iter().zip(ln_g).collect();
+        levels.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let (energies, mut ln_g): (Vec<f64>, Vec<f64>) = levels.into_iter().unzip();
+        let shift = ln_n_states - log_sum_exp(&ln_g);
+        ln_g.iter_mut().for_each(|x| *x += shift);
+        DensityOfStates { energies, ln_g }
+    }
+
+    /// ln Z = ln ∑ g(E) exp(-βE)
+    pub fn ln_partition_function(&self, beta: f64) -> f64 {
+        log_sum_exp(&self.boltzmann_log_weights(beta).collect::<Vec<f64>>())
+    }
+
+    /// Free energy F = -ln Z / β
+    pub fn free_energy(&self, beta: f64) -> f64 {
+        -self.ln_partition_function(beta) / beta
+    }
+
+    /// Internal energy U = <E>
+    pub fn internal_energy(&self, beta: f64) -> f64 {
+        self.energy_moments(beta).0
+    }
+
+    /// Entropy S = β (U - F), in units of k_B
+    pub fn entropy(&self, beta: f64) -> f64 {
+        self.ln_partition_function(beta) + beta * self.internal_energy(beta)
+    }
+
+    /// Specific heat of the whole system C = β² (<E²> - <E>²)
+    pub fn specific_heat(&self, beta: f64) -> f64 {
+        let (mean, mean_sq) = self.energy_moments(beta);
+        beta.powi(2) * (mean_sq - mean.powi(2))
+    }
+
+    /// ln (g(E) exp(-βE)) for every level
+    fn boltzmann_log_weights(&self, beta: f64) -> impl Iterator<Item = f64> + '_ {
+        self.ln_g.iter().zip(self.energies.iter()).map(move |(ln_g, e)| ln_g - beta * e)
+    }
+
+    /// (<E>, <E²>) in the canonical ensemble at beta
+    fn energy_moments(&self, beta: f64) -> (f64, f64) {
+        let ln_z = self.ln_partition_function(beta);
+        self.boltzmann_log_weights(beta)
+            .zip(self.energies.iter())
+            .fold((0.0, 0.0), |(m1, m2), (w, e)| {
+                let p = (w - ln_z).exp();
+                (m1 + p * e, m2 + p * e * e)
+            })
+    }
+}
+
+/// ln ∑ exp(x_i), without overflow
+fn log_sum_exp(xs: &[f64]) -> f64 {
+    let max = xs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    max + xs.iter().map(|x| (x - max).exp()).sum::<f64>().ln()
+}
+
+/// Estimates the density of states of the lattice at h = 0, i.e. over
+/// the levels E = -J ∑ s_i s_j (the field of the lattice is ignored,
+/// and so is its beta)
+///
+/// The sum runs over neighbouring pairs, each counted once, so E is the
+/// energy the Metropolis updates sample with, and half of
+/// `measure_energy`, which counts every pair from both ends.
+///
+/// The lattice is left in the last visited configuration. Only levels
+/// the walk has visited appear in the result. The walk draws from the
+/// lattice's random number generator, so a seeded lattice gives the
+/// same estimate.
+pub fn wang_landau(lattice: &mut Lattice2d, params: &WangLandauParams) -> DensityOfStates {
+    let n_sites = lattice.n_sites as usize;
+    lattice.set_tracking(true);
+    // the bond sum changes in steps of 4, from -2N to 2N
+    let bin = |bond_sum: i32| ((bond_sum + 2 * n_sites as i32) / 4) as usize;
+    let n_bins = n_sites + 1;
+    let mut ln_g = vec![0.0f64; n_bins];
+    let mut visited = vec![false; n_bins];
+    let mut histogram = vec![0usize; n_bins];
+    let mut ln_f = params.ln_f_initial;
+    let mut one_over_t = false;
+    let mut sweeps = 0usize;
+    let mut rng = lattice.fork_rng();
+
+    let mut bond_sum = lattice.tracked_bond_sum().unwrap();
+    while ln_f > params.ln_f_final {
+        for _ in 0..params.sweeps_between_checks {
+            for _ in 0..n_sites {
+                let idx0 = rng.gen_range(0..lattice.dims[0]);
+                let idx1 = rng.gen_range(0..lattice.dims[1]);
+                let new_bond_sum = bond_sum
+                    - 2 * lattice.nodes[[idx0, idx1]] * lattice.neighbour_spin_sum(idx0, idx1);
+                let d_ln_g = ln_g[bin(bond_sum)] - ln_g[bin(new_bond_sum)];
+                if d_ln_g >= 0.0 || rng.gen::<f64>() < d_ln_g.exp() {
+                    lattice.flip(idx0, idx1);
+                    bond_sum = new_bond_sum;
+                }
+                let b = bin(bond_sum);
+                ln_g[b] += ln_f;
+                histogram[b] += 1;
+                visited[b] = true;
+            }
+            sweeps += 1;
+            if one_over_t {
+                ln_f = 1.0 / sweeps as f64;
+            }
+        }
+        if !one_over_t && is_flat(&histogram, &visited, params.flatness) {
+            ln_f /= 2.0;
+            histogram.iter_mut().for_each(|h| *h = 0);
+            if params.schedule == ModificationSchedule::OneOverT && ln_f < 1.0 / sweeps as f64 {
+                one_over_t = true;
+            }
+        }
+    }
+
+    let (energies, ln_g): (Vec<f64>, Vec<f64>) = (0..n_bins)
+        .filter(|&b| visited[b])
+        .map(|b| (-lattice.j * (4 * b as i32 - 2 * n_sites as i32) as f64, ln_g[b]))
+        .unzip();
+    DensityOfStates::new(energies, ln_g, n_sites as f64 * 2f64.ln())
+}
+
+/// Returns true if every visited level was hit at least flatness times
+/// the mean number of hits
+fn is_flat(histogram: &[usize], visited: &[bool], flatness: f64) -> bool {
+    let hits: Vec<usize> = histogram.iter()
+        .zip(visited.iter())
+        .filter(|(_, &v)| v)
+        .map(|(&h, _)| h)
+        .collect();
+    let mean = hits.iter().sum::<usize>() as f64 / hits.len() as f64;
+    hits.iter().all(|&h| h as f64 >= flatness * mean)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::exact;
+
+    #[test]
+    fn test_density_of_states_thermodynamics() {
+        // a two level system with E = ±1
+        let dos = DensityOfStates::new(vec![1.0, -1.0], vec![0.0, 0.0], 2f64.ln());
+        assert_eq!(dos.energies, vec![-1.0, 1.0]);
+        let beta = 0.7;
+        assert!((dos.ln_partition_function(beta) - (2.0 * beta.cosh()).ln()).abs() < 1e-12);
+        assert!((dos.internal_energy(beta) + beta.tanh()).abs() < 1e-12);
+        assert!((dos.specific_heat(beta) - (beta / beta.cosh()).powi(2)).abs() < 1e-12);
+        assert!((dos.entropy(0.0) - 2f64.ln()).abs() < 1e-12);
+        let f = dos.free_energy(beta);
+        assert!((f - (dos.internal_energy(beta) - dos.entropy(beta) / beta)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_wang_landau() {
+        let mut lattice = Lattice2d::builder([4, 4]).seed(1).build().unwrap();
+        let params = WangLandauParams {
+            ln_f_final: 2e-5,
+            schedule: ModificationSchedule::OneOverT,
+            sweeps_between_checks: 50,
+            ..WangLandauParams::default()
+        };
+        let estimate = wang_landau(&mut lattice, &params);
+        let exact = exact::density_of_states([4, 4], 1.0);
+        assert_eq!(estimate.energies, exact.energies);
+        for (est, ex) in estimate.ln_g.iter().zip(exact.ln_g.iter()) {
+            assert!((est - ex).abs() < 0.25, "ln g: {} vs {}", est, ex);
+        }
+        let beta = 0.4;
+        let (c_est, c_ex) = (estimate.specific_heat(beta), exact.specific_heat(beta));
+        assert!((c_est - c_ex).abs() < 0.1 * c_ex, "C: {} vs {}", c_est, c_ex);
+    }
+}
@@ -5,9 +5,11 @@ use ising_lib::monte_carlo_measurement::{MonteCarlo, MonteCarloParams};
 fn integration_test_test() {
     let params = MonteCarloParams {
         n_runs: 5,
-        flips_to_skip: 100,
+        sweeps_to_skip: 2,
         samples_per_run: 3,
-        flips_to_skip_between_samples: 10,
+        sweeps_between_samples: 1,
+        max_threads: None,
+        start_from_current: false,
     };
     let mut lattice = Lattice2d::new(
         [8,9],